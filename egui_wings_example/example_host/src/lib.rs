@@ -11,4 +11,8 @@ pub mod on {
     /// Raised when the host is rendering a frame.
     #[export_type]
     pub struct Render;
+
+    /// Raised when the host is rendering the offscreen monitor.
+    #[export_type]
+    pub struct RenderMonitor;
 }
@@ -42,12 +42,43 @@ impl PluginSystem {
 
         self.gallery.show(&ctx, &mut true);
     }
+
+    /// Runs the pass hooks registered on this plugin's context.
+    fn begin_pass(&mut self, event: &egui_wings::on::BeginPass) {
+        self.ctx.get::<dyn Egui>().run_begin_pass_hooks(event);
+    }
+
+    /// Runs the pass hooks registered on this plugin's context, after all other UI.
+    fn end_pass(&mut self, event: &egui_wings::on::EndPass) {
+        self.ctx.get::<dyn Egui>().run_end_pass_hooks(event);
+    }
+
+    /// Submits the `egui` commands to draw the offscreen monitor.
+    fn draw_monitor(&mut self, _: &example_host::on::RenderMonitor) {
+        let egui = self.ctx.get::<dyn Egui>();
+        let Some(ctx) = egui.context_named("monitor") else {
+            return;
+        };
+
+        CentralPanel::default().show(&ctx, |ui| {
+            ui.heading("webassembly monitor");
+            ui.label(format!("Click count: {}", self.click_count));
+
+            if ui.button("Button!").clicked() {
+                self.click_count += 1;
+            }
+        });
+    }
 }
 
 impl WingsSystem for PluginSystem {
     const DEPENDENCIES: Dependencies = dependencies().with::<dyn Egui>();
 
-    const EVENT_HANDLERS: EventHandlers<Self> = event_handlers().with(Self::draw_ui);
+    const EVENT_HANDLERS: EventHandlers<Self> = event_handlers()
+        .with(Self::begin_pass)
+        .with(Self::draw_ui)
+        .with(Self::draw_monitor)
+        .with(Self::end_pass);
 
     fn new(ctx: WingsContextHandle<Self>) -> Self {
         Self {
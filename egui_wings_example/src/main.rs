@@ -46,7 +46,11 @@ impl Host for ExampleHostSystems {
     // Declare the egui system that should be exported to WASM.
     const SYSTEMS: Systems<Self> = systems().with::<EguiHost>(traits().with::<dyn Egui>());
 
-    const EVENTS: Events<Self> = events().with::<example_host::on::Render>();
+    const EVENTS: Events<Self> = events()
+        .with::<egui_wings::on::BeginPass>()
+        .with::<egui_wings::on::EndPass>()
+        .with::<example_host::on::Render>()
+        .with::<example_host::on::RenderMonitor>();
 
     type Engine = wasmtime_runtime_layer::Engine;
 
@@ -126,10 +130,18 @@ async fn run() {
 
     let scale_factor = 1.0;
 
+    // Create an offscreen surface, which plugins draw into like an in-game monitor
+    let mut monitor = OffscreenSurface::new([512, 384], 1.0);
+    let mut monitor_renderer =
+        OffscreenRenderer::new(&device, TextureFormat::Rgba8Unorm, monitor.size_in_pixels());
+    let monitor_texture = egui_renderer.register_native_texture(&device, monitor_renderer.view());
+
     let mut ctx = create_geese_context();
-    // Set the context that will be exposed to WASM plugins
+    // Set the contexts that will be exposed to WASM plugins
     ctx.get_mut::<EguiHost>()
         .set_context(egui_renderer.context().clone());
+    ctx.get_mut::<EguiHost>()
+        .register_context("monitor", monitor.context().clone());
 
     let _ = event_loop.run(move |event, elwt| {
         elwt.set_control_flow(ControlFlow::Poll);
@@ -187,6 +199,13 @@ async fn run() {
                         let mut encoder = device
                             .create_command_encoder(&CommandEncoderDescriptor { label: None });
 
+                        let monitor_frame = monitor.run(|_| {
+                            EguiHost::run_pass_hooks(&mut ctx, "monitor", |ctx| {
+                                ctx.flush().with(example_host::on::RenderMonitor);
+                            });
+                        });
+                        monitor_renderer.draw(&device, &queue, &mut encoder, monitor_frame);
+
                         let screen_descriptor = ScreenDescriptor {
                             size_in_pixels: [config.width, config.height],
                             pixels_per_point: window.scale_factor() as f32 * scale_factor,
@@ -199,8 +218,11 @@ async fn run() {
                             &window,
                             &surface_view,
                             screen_descriptor,
-                            |_| {
-                                ctx.flush().with(example_host::on::Render);
+                            |egui_ctx| {
+                                EguiHost::run_pass_hooks(&mut ctx, "", |ctx| {
+                                    ctx.flush().with(example_host::on::Render);
+                                    show_monitor(egui_ctx, monitor_texture, &mut monitor);
+                                });
                             },
                         );
 
@@ -228,6 +250,122 @@ async fn run() {
     });
 }
 
+/// Shows the offscreen monitor in a host window, forwarding the pointer input over it.
+fn show_monitor(ctx: &egui::Context, texture: egui::TextureId, monitor: &mut OffscreenSurface) {
+    egui::Window::new("Offscreen monitor").show(ctx, |ui| {
+        let [width, height] = monitor.size_in_pixels();
+        let image = egui::Image::new((texture, egui::vec2(width as f32, height as f32)));
+        let response = ui.add(image.sense(egui::Sense::click_and_drag()));
+
+        let pointer = ui
+            .input(|input| input.pointer.latest_pos())
+            .filter(|_| response.hovered() || response.dragged());
+        let Some(pos) = pointer else {
+            monitor.pointer_left();
+            return;
+        };
+
+        // Map the pointer to texture coordinates, as a ray hit against a mesh would
+        let uv = ((pos - response.rect.min) / response.rect.size()).to_pos2();
+        monitor.set_modifiers(ui.input(|input| input.modifiers));
+        monitor.pointer_moved(uv);
+        for event in ui.input(|input| input.events.clone()) {
+            match event {
+                egui::Event::PointerButton {
+                    button, pressed, ..
+                } => monitor.pointer_button(button, pressed),
+                egui::Event::MouseWheel { delta, .. } => monitor.scroll(delta),
+                _ => {}
+            }
+        }
+    });
+}
+
+/// Renders the frames of an offscreen surface into a texture.
+pub struct OffscreenRenderer {
+    renderer: Renderer,
+    view: TextureView,
+}
+
+impl OffscreenRenderer {
+    pub fn new(device: &Device, format: TextureFormat, size_in_pixels: [u32; 2]) -> Self {
+        let [width, height] = size_in_pixels;
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("egui offscreen texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        Self {
+            renderer: Renderer::new(device, format, RendererOptions::default()),
+            view: texture.create_view(&TextureViewDescriptor::default()),
+        }
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        frame: OffscreenFrame,
+    ) {
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: frame.size_in_pixels,
+            pixels_per_point: frame.pixels_per_point,
+        };
+
+        for (id, image_delta) in &frame.textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+        self.renderer.update_buffers(
+            device,
+            queue,
+            encoder,
+            &frame.primitives,
+            &screen_descriptor,
+        );
+        {
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                label: Some("egui offscreen render pass"),
+                occlusion_query_set: None,
+            });
+            self.renderer.render(
+                &mut rpass.forget_lifetime(),
+                &frame.primitives,
+                &screen_descriptor,
+            );
+        }
+        for x in &frame.textures_delta.free {
+            self.renderer.free_texture(x)
+        }
+    }
+}
+
 pub struct EguiRenderer {
     state: egui_winit::State,
     renderer: Renderer,
@@ -267,6 +405,15 @@ impl EguiRenderer {
         }
     }
 
+    pub fn register_native_texture(
+        &mut self,
+        device: &Device,
+        view: &TextureView,
+    ) -> egui::TextureId {
+        self.renderer
+            .register_native_texture(device, view, FilterMode::Linear)
+    }
+
     pub fn handle_input(&mut self, window: &winit::window::Window, event: &WindowEvent) {
         let _ = self.state.on_window_event(window, event);
     }
@@ -332,3 +479,45 @@ impl EguiRenderer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui_kittest::kittest::*;
+
+    #[test]
+    fn plugin_button_counts_clicks() {
+        let mut harness = wings_harness::<ExampleHostSystems>(
+            egui_kittest::Harness::builder().with_size(egui::vec2(1024.0, 768.0)),
+            |host, image| {
+                let plugin = host
+                    .load(EXAMPLE_PLUGIN_WASM)
+                    .expect("Failed to load plugin.");
+                image.add::<ExampleHost>(&plugin);
+            },
+            |_, ctx| {
+                EguiHost::run_pass_hooks(ctx, "", |ctx| {
+                    ctx.flush().with(example_host::on::Render);
+                });
+            },
+        );
+        harness
+            .state_mut()
+            .flush()
+            .with(geese::notify::add_system::<EguiExample>());
+        harness.run();
+
+        assert!(harness.query_by_label("Click count: 0").is_none());
+        harness
+            .get_by_role_and_label(egui::accesskit::Role::Window, "webassembly says hello!")
+            .get_by_label("Show")
+            .click();
+        harness.run();
+        harness.get_by_label("Click count: 0");
+
+        harness.get_by_label("Button!").click();
+        harness.run();
+        harness.get_by_label("Click count: 1");
+        assert!(harness.query_by_label("Click count: 0").is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use egui_wings::egui::*;
+use egui_wings::*;
+use std::collections::*;
+
+/// A widget which a plugin added to one of its layers, as seen by the host.
+#[derive(Clone, Debug)]
+pub struct PluginWidget {
+    /// The name of the context in which the widget was added.
+    pub context: String,
+    /// The viewport in which the widget was added.
+    pub viewport_id: ViewportId,
+    /// The widget's identifier.
+    pub id: Id,
+    /// The layer to which the widget was added.
+    pub layer_id: LayerId,
+    /// The widget's rectangle, in screen space.
+    pub rect: Rect,
+    /// The area in which the widget responds to the pointer, in screen space.
+    pub interact_rect: Rect,
+    /// The interactions which the widget senses.
+    pub sense: Sense,
+    /// Whether the widget was enabled.
+    pub enabled: bool,
+    /// The widget's type, label and value, if they were recorded. These are recorded when
+    /// AccessKit is enabled on the host context and `egui_wings` is built with the `accesskit` feature.
+    pub info: Option<WidgetInfo>,
+}
+
+impl PluginWidget {
+    /// Gets the widget's label, if it was recorded.
+    pub fn label(&self) -> Option<&str> {
+        self.info.as_ref().and_then(|x| x.label.as_deref())
+    }
+
+    /// Gets the widget's type, if it was recorded.
+    pub fn widget_type(&self) -> Option<WidgetType> {
+        self.info.as_ref().map(|x| x.typ)
+    }
+}
+
+/// Gets the widgets that were added to the given layers during the most recent pass of a context.
+pub(crate) fn plugin_widgets(
+    ctx: &Context,
+    name: &str,
+    owned: impl Fn(&LayerId) -> bool,
+) -> Vec<PluginWidget> {
+    context_widgets(ctx)
+        .into_iter()
+        .filter(|(_, widget, _)| owned(&widget.layer_id))
+        .map(|(viewport_id, widget, info)| PluginWidget {
+            context: name.to_owned(),
+            viewport_id,
+            id: widget.id,
+            layer_id: widget.layer_id,
+            rect: widget.rect,
+            interact_rect: widget.interact_rect,
+            sense: widget.sense,
+            enabled: widget.enabled,
+            info,
+        })
+        .collect()
+}
+
+/// Gets the events which click the primary pointer button over a widget.
+pub(crate) fn click_events(widget: &PluginWidget) -> Vec<Event> {
+    let pos = widget.interact_rect.center();
+    vec![
+        Event::PointerMoved(pos),
+        pointer_button(pos, true),
+        pointer_button(pos, false),
+    ]
+}
+
+/// Gets the events which drag a widget by the given offset, one batch per pass.
+pub(crate) fn drag_events(widget: &PluginWidget, delta: Vec2) -> Vec<Vec<Event>> {
+    let start = widget.interact_rect.center();
+    let end = start + delta;
+    vec![
+        vec![Event::PointerMoved(start), pointer_button(start, true)],
+        vec![Event::PointerMoved(end)],
+        vec![pointer_button(end, false)],
+    ]
+}
+
+/// Creates an event which presses or releases the primary pointer button.
+fn pointer_button(pos: Pos2, pressed: bool) -> Event {
+    Event::PointerButton {
+        pos,
+        button: PointerButton::Primary,
+        pressed,
+        modifiers: Modifiers::NONE,
+    }
+}
+
+/// Queues batches of events for delivery to a viewport of the given context. One batch is
+/// delivered during each pass, after any batches which were queued previously.
+pub(crate) fn queue_events(ctx: &Context, viewport_id: ViewportId, passes: Vec<Vec<Event>>) {
+    ctx.add_plugin(AutomationInput::default());
+    ctx.with_plugin(|input: &mut AutomationInput| {
+        input.passes.entry(viewport_id).or_default().extend(passes);
+    });
+    ctx.request_repaint_of(viewport_id);
+}
+
+/// Adds synthesized events to the input of a context.
+#[derive(Default)]
+struct AutomationInput {
+    /// The batches of events which have yet to be delivered to each viewport.
+    passes: HashMap<ViewportId, VecDeque<Vec<Event>>>,
+}
+
+impl Plugin for AutomationInput {
+    fn debug_name(&self) -> &'static str {
+        "egui_wings_host::AutomationInput"
+    }
+
+    fn on_end_pass(&mut self, ctx: &Context) {
+        for (viewport_id, passes) in &self.passes {
+            if !passes.is_empty() {
+                ctx.request_repaint_of(*viewport_id);
+            }
+        }
+    }
+
+    fn input_hook(&mut self, input: &mut RawInput) {
+        if let Some(events) = self
+            .passes
+            .get_mut(&input.viewport_id)
+            .and_then(VecDeque::pop_front)
+        {
+            input.events.extend(events);
+        }
+    }
+}
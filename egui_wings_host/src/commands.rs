@@ -0,0 +1,13 @@
+use egui_wings::*;
+
+/// A command that a plugin sent to the host from its UI.
+#[derive(Clone, Debug)]
+pub struct ReceivedCommand {
+    /// The plugin which sent the command.
+    pub plugin: PluginId,
+    /// The name of the context in which the command was sent. The empty name
+    /// refers to the default context.
+    pub context: String,
+    /// The command, which may be decoded with [`PluginCommand::decode`].
+    pub command: PluginCommand,
+}
@@ -0,0 +1,276 @@
+use egui_wings::egui::*;
+use std::path::*;
+
+/// The environment variable which, when set, causes golden images to be overwritten with
+/// the rendered images instead of being compared against them.
+pub const UPDATE_GOLDEN_VARIABLE: &str = "UPDATE_SNAPSHOTS";
+
+/// Determines how much a rendered image may differ from its golden image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GoldenTolerance {
+    /// The largest difference in any color channel for which two pixels are considered equal.
+    pub channel: u8,
+    /// The number of pixels which may differ before the comparison fails.
+    pub pixels: usize,
+}
+
+impl Default for GoldenTolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            pixels: 0,
+        }
+    }
+}
+
+/// Describes why a rendered image did not match its golden image.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// The golden image does not exist. The rendered image was written next to it.
+    Missing(PathBuf),
+    /// The golden image could not be read or the rendered image could not be written.
+    Io(std::io::Error),
+    /// The golden image was not a supported PNG file.
+    Decode(String),
+    /// The golden image has a different size than the rendered image.
+    SizeMismatch {
+        /// The size of the golden image.
+        expected: [usize; 2],
+        /// The size of the rendered image.
+        actual: [usize; 2],
+    },
+    /// Too many pixels differed between the images. The rendered image and an image
+    /// highlighting the differences were written next to the golden image.
+    Mismatch {
+        /// The number of pixels which differed.
+        differing: usize,
+        /// The path of the image highlighting the differences.
+        diff: PathBuf,
+    },
+}
+
+impl std::fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(path) => write!(
+                f,
+                "golden image was missing; the rendered image was written to {}",
+                path.display()
+            ),
+            Self::Io(error) => write!(f, "failed to access golden image: {error}"),
+            Self::Decode(error) => write!(f, "failed to decode golden image: {error}"),
+            Self::SizeMismatch { expected, actual } => write!(
+                f,
+                "rendered image was {actual:?} pixels, but golden image was {expected:?}"
+            ),
+            Self::Mismatch { differing, diff } => write!(
+                f,
+                "{differing} pixels differed from golden image; see {}",
+                diff.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<std::io::Error> for GoldenError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Compares a rendered image against the golden image at the given path, which should be
+/// a PNG file. If the images differ by more than the tolerance, the rendered image is
+/// written alongside the golden image with the extension `new.png`, and the differing
+/// pixels are highlighted in an image with the extension `diff.png`. If the
+/// [`UPDATE_GOLDEN_VARIABLE`] environment variable is set, the golden image is replaced
+/// with the rendered image instead.
+pub fn compare_golden(
+    image: &ColorImage,
+    path: impl AsRef<Path>,
+    tolerance: GoldenTolerance,
+) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+    let new_path = path.with_extension("new.png");
+    let diff_path = path.with_extension("diff.png");
+
+    if std::env::var_os(UPDATE_GOLDEN_VARIABLE).is_some() {
+        write_png(image, path)?;
+        remove_if_exists(&new_path)?;
+        return remove_if_exists(&diff_path);
+    }
+
+    let golden = match std::fs::read(path) {
+        Ok(golden) => golden,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            write_png(image, &new_path)?;
+            return Err(GoldenError::Missing(new_path));
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let (expected_size, expected) = decode_png(&golden).map_err(GoldenError::Decode)?;
+    if expected_size != image.size {
+        write_png(image, &new_path)?;
+        return Err(GoldenError::SizeMismatch {
+            expected: expected_size,
+            actual: image.size,
+        });
+    }
+
+    let actual = unmultiplied_rgba(image);
+    let mut differing = 0;
+    let diff = actual
+        .chunks_exact(4)
+        .zip(expected.chunks_exact(4))
+        .map(|(a, b)| {
+            let distance = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max();
+            if distance.unwrap_or_default() > tolerance.channel {
+                differing += 1;
+                Color32::RED
+            } else {
+                Color32::from_rgba_unmultiplied(b[0], b[1], b[2], b[3]).gamma_multiply(0.25)
+            }
+        })
+        .collect();
+
+    if differing > tolerance.pixels {
+        write_png(image, &new_path)?;
+        write_png(&ColorImage::new(image.size, diff), &diff_path)?;
+        Err(GoldenError::Mismatch {
+            differing,
+            diff: diff_path,
+        })
+    } else {
+        remove_if_exists(&new_path)?;
+        remove_if_exists(&diff_path)
+    }
+}
+
+/// Writes an image to the given path as a PNG file.
+pub fn write_png(image: &ColorImage, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, image.width() as u32, image.height() as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+    writer
+        .write_image_data(&unmultiplied_rgba(image))
+        .map_err(std::io::Error::other)?;
+    writer.finish().map_err(std::io::Error::other)?;
+    std::fs::write(path, data)
+}
+
+/// Reads an image from a PNG file at the given path.
+pub fn read_png(path: impl AsRef<Path>) -> Result<ColorImage, GoldenError> {
+    let (size, rgba) = decode_png(&std::fs::read(path)?).map_err(GoldenError::Decode)?;
+    Ok(ColorImage::from_rgba_unmultiplied(size, &rgba))
+}
+
+/// Decodes a PNG file into its size and RGBA bytes with unmultiplied alpha.
+fn decode_png(data: &[u8]) -> Result<([usize; 2], Vec<u8>), String> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|x| x.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|x| x.to_string())?;
+    buffer.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|x| [x[0], x[1], x[2], u8::MAX])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|x| [x[0], x[0], x[0], x[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&x| [x, x, x, u8::MAX]).collect(),
+        png::ColorType::Indexed => return Err("indexed colors were not expanded".to_string()),
+    };
+
+    Ok(([info.width as usize, info.height as usize], rgba))
+}
+
+/// Converts the premultiplied pixels of an image to RGBA bytes with unmultiplied alpha.
+fn unmultiplied_rgba(image: &ColorImage) -> Vec<u8> {
+    image
+        .pixels
+        .iter()
+        .flat_map(|x| x.to_srgba_unmultiplied())
+        .collect()
+}
+
+/// Removes the file at the given path, if there is one.
+fn remove_if_exists(path: &Path) -> Result<(), GoldenError> {
+    match std::fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty directory for the images written by a test.
+    fn test_directory(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("egui_wings_golden_{name}"));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("Failed to create test directory.");
+        path
+    }
+
+    /// Creates a small image with distinct, partially transparent pixels.
+    fn test_image() -> ColorImage {
+        ColorImage::new(
+            [2, 2],
+            vec![
+                Color32::RED,
+                Color32::from_rgba_unmultiplied(0, 255, 0, 128),
+                Color32::TRANSPARENT,
+                Color32::WHITE,
+            ],
+        )
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let path = test_directory("round_trip").join("image.png");
+        let image = test_image();
+        write_png(&image, &path).expect("Failed to write image.");
+        let read = read_png(&path).expect("Failed to read image.");
+        assert_eq!(read.size, image.size);
+        assert_eq!(unmultiplied_rgba(&read), unmultiplied_rgba(&image));
+    }
+
+    #[test]
+    fn missing_golden_writes_rendered_image() {
+        let path = test_directory("missing").join("image.png");
+        let error = compare_golden(&test_image(), &path, GoldenTolerance::default());
+        assert!(matches!(error, Err(GoldenError::Missing(x)) if x.exists()));
+    }
+
+    #[test]
+    fn differing_pixels_are_reported() {
+        let path = test_directory("mismatch").join("image.png");
+        let image = test_image();
+        write_png(&image, &path).expect("Failed to write image.");
+        compare_golden(&image, &path, GoldenTolerance::default()).expect("Images differed.");
+
+        let mut changed = image.clone();
+        changed.pixels[3] = Color32::BLACK;
+        let error = compare_golden(&changed, &path, GoldenTolerance::default());
+        assert!(
+            matches!(error, Err(GoldenError::Mismatch { differing: 1, diff }) if diff.exists())
+        );
+
+        let tolerance = GoldenTolerance {
+            channel: 2,
+            pixels: 1,
+        };
+        compare_golden(&changed, &path, tolerance).expect("Tolerance was not applied.");
+    }
+}
@@ -0,0 +1,13 @@
+/// Determines which of the host's shapes are sent to a plugin.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GraphicsPolicy {
+    /// The plugin receives the shapes of every layer, including those painted by the host
+    /// and by other plugins.
+    #[default]
+    Shared,
+    /// The plugin receives only the shapes of the layers that it owns. Shapes that it paints
+    /// onto other layers are added after the shapes which the host and other plugins painted
+    /// there. This prevents plugins from reading text that is displayed by the host, such as
+    /// the contents of a password field, and reduces the amount of data sent to plugins.
+    Owned,
+}
@@ -0,0 +1,47 @@
+use egui_wings::egui::*;
+use egui_wings::*;
+use std::sync::*;
+
+/// Determines which of the host's input is delivered to a plugin.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum InputPolicy {
+    /// The plugin receives all input for every viewport.
+    #[default]
+    Shared,
+    /// The plugin receives keyboard, text and clipboard input only while one of its
+    /// widgets has focus, and pointer input only while the pointer is over one of its
+    /// layers or it is dragging a widget. Keyboard events accepted by the host's
+    /// shortcut filter are delivered regardless of focus.
+    Routed,
+}
+
+/// Decides whether a keyboard event should be delivered to a plugin which does not have focus.
+pub type ShortcutFilter = Arc<dyn Fn(PluginId, &Event) -> bool + Send + Sync>;
+
+/// Determines whether files dragged onto the host are delivered to a plugin.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FileDropPolicy {
+    /// The plugin receives the files which are dragged or dropped over one of its layers.
+    /// The plugin learns the name and type of each file, but not its path, and may read a
+    /// dropped file's contents through the host.
+    #[default]
+    Target,
+    /// The plugin never receives dragged or dropped files.
+    Denied,
+}
+
+/// Determines how the host's input is sent to plugins at the beginning of a transaction.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum InputSyncMode {
+    /// The plugin receives the host's processed input state, including any changes that
+    /// the host or other plugins made to it earlier in the pass, such as consumed events.
+    #[default]
+    Processed,
+    /// The plugin receives the raw input of each pass once, and rebuilds the input state
+    /// itself by running the same processing as the host. Later transactions in the same
+    /// pass send no input at all. Changes that the host or other plugins make to the input
+    /// state during the pass are not visible to the plugin, and changes that the plugin
+    /// makes are not visible to the host. The processed input state is sent instead when
+    /// the plugin did not take part in the previous pass, or when input is withheld from it.
+    Raw,
+}
@@ -0,0 +1,46 @@
+use crate::*;
+use egui_kittest::*;
+use egui_wings::egui::*;
+use geese::*;
+use wings_host::*;
+
+/// Creates a `kittest` harness for testing the UIs of plugins loaded into a headless
+/// `WingsHost`. During each frame, the harness context is shared with plugins through the
+/// [`EguiHost`] in `geese`, and `run_ui` is invoked so that plugins build their UI, typically
+/// by raising an event. Widgets drawn by plugins may then be queried by label or role, clicked,
+/// and inspected exactly like native widgets. Plugins should enable the `accesskit` feature of
+/// `egui_wings` so that their widgets are visible to the harness.
+pub fn plugin_harness<'a>(
+    builder: HarnessBuilder<GeeseContext>,
+    geese: GeeseContext,
+    mut run_ui: impl FnMut(&Context, &mut GeeseContext) + 'a,
+) -> Harness<'a, GeeseContext> {
+    builder.build_state(
+        move |ctx, geese| {
+            geese.get_mut::<EguiHost>().set_context(ctx.clone());
+            run_ui(ctx, geese);
+        },
+        geese,
+    )
+}
+
+/// Creates a headless `GeeseContext` containing a `WingsHost`, and a `kittest` harness which
+/// drives it as described by [`plugin_harness`]. The plugins to test are loaded by `load`,
+/// which adds them to the image that the host instantiates. The host's systems must export
+/// [`EguiHost`] to plugins.
+pub fn wings_harness<'a, H: Host>(
+    builder: HarnessBuilder<GeeseContext>,
+    load: impl FnOnce(&mut WingsHost<H>, &mut WingsImage),
+    run_ui: impl FnMut(&Context, &mut GeeseContext) + 'a,
+) -> Harness<'a, GeeseContext> {
+    let mut geese = GeeseContext::default();
+    geese.flush().with(notify::add_system::<WingsHost<H>>());
+
+    let mut host = geese.get_mut::<WingsHost<H>>();
+    let mut image = WingsImage::default();
+    load(&mut host, &mut image);
+    host.instantiate(&image);
+    drop(host);
+
+    plugin_harness(builder, geese, run_ui)
+}
@@ -5,15 +5,72 @@
 //! Provides a host system implementation of [`egui_wings::Egui`] with which WASM
 //! applications may interact.
 
+pub use crate::automation::*;
+pub use crate::commands::*;
+#[cfg(feature = "golden")]
+pub use crate::golden::*;
+pub use crate::graphics::*;
+pub use crate::input::*;
+#[cfg(feature = "kittest")]
+pub use crate::kittest::*;
+pub use crate::metrics::*;
+pub use crate::offscreen::*;
+#[cfg(feature = "golden")]
+pub use crate::raster::*;
+use crate::watchdog::*;
 use egui_wings::egui::*;
 pub use egui_wings::Egui;
 use egui_wings::*;
 use geese::*;
+use serde::de::*;
+use serde::*;
+use std::collections::*;
+use std::sync::*;
+use std::time::*;
+
+/// Allows automation scripts to find and operate the widgets of plugins.
+mod automation;
+
+/// Describes the commands that plugins send to the host.
+mod commands;
+
+/// Compares rendered images against stored golden images.
+#[cfg(feature = "golden")]
+mod golden;
+
+/// Defines policies for sharing shapes with plugins.
+mod graphics;
+
+/// Defines policies for delivering input to plugins.
+mod input;
+
+/// Allows plugin UIs to be tested with `egui_kittest`.
+#[cfg(feature = "kittest")]
+mod kittest;
+
+/// Defines counters that describe plugin transactions.
+mod metrics;
+
+/// Draws contexts offscreen so that they may be rendered into textures.
+mod offscreen;
+
+/// Renders tessellated output into images without a GPU.
+#[cfg(feature = "golden")]
+mod raster;
+
+/// Interrupts plugins which exceed their time budgets.
+mod watchdog;
 
 /// Implements the `egui_wings::Egui` trait for WASM guest modules.
 pub struct EguiHost {
+    /// Additional `egui` contexts which WASM modules may select by name.
+    contexts: HashMap<String, Context>,
     /// The `egui` context to share with WASM modules.
     ctx: Context,
+    /// The host-side state of each plugin.
+    plugins: Mutex<PluginRegistry>,
+    /// Interrupts plugins which exceed their time budgets.
+    watchdog: Watchdog,
 }
 
 impl EguiHost {
@@ -26,6 +83,738 @@ impl EguiHost {
     pub fn set_context(&mut self, ctx: Context) {
         self.ctx = ctx;
     }
+
+    /// Gets a reference to the context registered under the given name, if any.
+    /// The empty name refers to the default context.
+    pub fn context_named(&self, name: &str) -> Option<&Context> {
+        if name.is_empty() {
+            Some(&self.ctx)
+        } else {
+            self.contexts.get(name)
+        }
+    }
+
+    /// Registers an additional context which WASM modules may select by name, such as a
+    /// context for an in-world panel. Each context is synchronized with plugins independently.
+    /// Any context previously registered under the name is replaced. Panics if the name is
+    /// empty, since the empty name refers to the default context.
+    pub fn register_context(&mut self, name: impl Into<String>, ctx: Context) {
+        let name = name.into();
+        assert!(!name.is_empty(), "Context name must not be empty.");
+        self.contexts.insert(name, ctx);
+    }
+
+    /// Removes the context registered under the given name, returning it if it existed.
+    pub fn unregister_context(&mut self, name: &str) -> Option<Context> {
+        self.contexts.remove(name)
+    }
+
+    /// Raises [`on::BeginPass`] and [`on::EndPass`] for the named context around `run_ui`, so
+    /// that the pass hooks of plugins run at the beginning and end of the pass. This should be
+    /// called within the pass of every context that plugins use, including those registered
+    /// with [`Self::register_context`]. Plugins which registered no hooks ignore these events.
+    pub fn run_pass_hooks<R>(
+        ctx: &mut GeeseContext,
+        name: &str,
+        run_ui: impl FnOnce(&mut GeeseContext) -> R,
+    ) -> R {
+        ctx.flush().with(on::BeginPass {
+            context: name.to_owned(),
+        });
+        let result = run_ui(ctx);
+        ctx.flush().with(on::EndPass {
+            context: name.to_owned(),
+        });
+        result
+    }
+
+    /// Gets the transaction counters for every plugin that has registered with the host.
+    pub fn metrics(&self) -> EguiMetrics {
+        let plugins = self.plugins();
+        EguiMetrics {
+            frame_nr: plugins.frame_nr,
+            plugins: plugins
+                .plugins
+                .iter()
+                .map(|(id, state)| {
+                    let mut metrics = state.metrics.clone();
+                    metrics.advance_to(plugins.frame_nr);
+                    (*id, metrics)
+                })
+                .collect(),
+        }
+    }
+
+    /// Gets the plugin which first drew the given layer, if any. Layers that were
+    /// first drawn by the host are not owned by a plugin.
+    pub fn layer_owner(&self, layer: LayerId) -> Option<PluginId> {
+        self.layer_owner_in("", layer)
+    }
+
+    /// Gets the plugin which first drew the given layer in the named context, if any.
+    pub fn layer_owner_in(&self, context: &str, layer: LayerId) -> Option<PluginId> {
+        self.plugins()
+            .layer_owners
+            .get(&(context.to_owned(), layer))
+            .copied()
+    }
+
+    /// Gets the widgets which the given plugin added to its own layers during the most
+    /// recently completed pass of the default context, ordered from back to front.
+    pub fn plugin_widgets(&self, plugin: PluginId) -> Vec<PluginWidget> {
+        self.plugin_widgets_in("", plugin)
+    }
+
+    /// Gets the widgets which the given plugin added to its own layers during the most
+    /// recently completed pass of the named context, ordered from back to front.
+    pub fn plugin_widgets_in(&self, context: &str, plugin: PluginId) -> Vec<PluginWidget> {
+        let Some(ctx) = self.context_named(context) else {
+            return Vec::new();
+        };
+
+        let plugins = self.plugins();
+        automation::plugin_widgets(ctx, context, |layer| {
+            plugins.layer_owners.get(&(context.to_owned(), *layer)) == Some(&plugin)
+        })
+    }
+
+    /// Clicks the given widget with the primary pointer button during the next pass
+    /// of the context in which it was added.
+    pub fn click_widget(&self, widget: &PluginWidget) {
+        self.queue_widget_events(widget, vec![automation::click_events(widget)]);
+    }
+
+    /// Clicks the given widget to focus it, and then types the given text into it
+    /// during the following pass.
+    pub fn type_into_widget(&self, widget: &PluginWidget, text: impl Into<String>) {
+        self.queue_widget_events(
+            widget,
+            vec![
+                automation::click_events(widget),
+                vec![Event::Text(text.into())],
+            ],
+        );
+    }
+
+    /// Drags the given widget with the primary pointer button by the given offset in points.
+    /// The drag is pressed, moved and released over the next three passes.
+    pub fn drag_widget(&self, widget: &PluginWidget, delta: Vec2) {
+        self.queue_widget_events(widget, automation::drag_events(widget, delta));
+    }
+
+    /// Requests that every plugin with a persistence key report its `egui` memory
+    /// during its next transaction. The memory will be included in calls to
+    /// [`Self::save_plugin_states`] made after that transaction, so the host should
+    /// request memory at least one frame before saving.
+    pub fn request_plugin_memory(&self) {
+        for state in self.plugins().plugins.values_mut() {
+            state.save_requested = state.key.is_some();
+        }
+    }
+
+    /// Gets the persisted state of every plugin, keyed by the persistence key that
+    /// each plugin provided. The result includes state loaded with [`Self::load_plugin_states`]
+    /// for plugins which have not been instantiated. A plugin's memory is updated only if it
+    /// reported it after [`Self::request_plugin_memory`]; otherwise, the memory that was
+    /// loaded for it is kept.
+    pub fn save_plugin_states(&self) -> BTreeMap<String, PersistedPluginState> {
+        let keyed_plugins = {
+            let plugins = self.plugins();
+            plugins
+                .plugins
+                .iter()
+                .filter_map(|(id, state)| {
+                    let layers = plugins.owned_layers("", *id);
+                    Some((state.key.clone()?, layers, state.memory.clone()))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let captured = keyed_plugins
+            .into_iter()
+            .map(|(key, layers, memory)| {
+                let areas = PersistedPluginState::capture_areas(&self.ctx, layers);
+                (key, PersistedPluginState { areas, memory })
+            })
+            .collect::<Vec<_>>();
+
+        let mut plugins = self.plugins();
+        for (key, mut state) in captured {
+            if state.memory.is_none() {
+                state.memory = plugins.persisted.get(&key).and_then(|x| x.memory.clone());
+            }
+            plugins.persisted.insert(key, state);
+        }
+        plugins.persisted.clone()
+    }
+
+    /// Loads persisted plugin state, which was previously obtained from [`Self::save_plugin_states`].
+    /// The state is restored to the host and guest when a plugin sets the matching persistence key.
+    pub fn load_plugin_states(&self, states: BTreeMap<String, PersistedPluginState>) {
+        self.plugins().persisted = states;
+    }
+
+    /// Takes the commands that plugins have sent since the last call, in the order that
+    /// they were received. Commands are received when a plugin's edits are applied, so the
+    /// commands of a plugin whose edits were discarded for exceeding its time budget are
+    /// dropped, and the commands sent during a deferred frame are received in order of
+    /// plugin identifier when the frame ends.
+    pub fn take_commands(&self) -> Vec<ReceivedCommand> {
+        std::mem::take(&mut self.plugins().commands)
+    }
+
+    /// Registers a widget implementation that plugins may place into their UI with
+    /// `HostWidget`, such as a native file picker or a color picker tied to project palettes.
+    /// When a plugin's edits are applied, the widget is drawn into the space that the plugin
+    /// reserved and may change the value that the plugin provided. The value and response are
+    /// delivered to the plugin during its next transaction. Widgets are only drawn into
+    /// layers that the plugin owns.
+    pub fn register_widget<T: Serialize + DeserializeOwned>(
+        &self,
+        name: impl Into<String>,
+        widget: impl Fn(&mut Ui, &mut T) -> Response + Send + Sync + 'static,
+    ) {
+        let widget: HostWidgetFn =
+            Arc::new(move |ctx, placement| placement.show(ctx, |ui, value| widget(ui, value)));
+        self.plugins().widgets.insert(name.into(), widget);
+    }
+
+    /// Sets the URI schemes (such as `file` or `https`) which plugins may load images from
+    /// using the loaders installed on the host context. By default, plugins may only load
+    /// images whose bytes they provide themselves.
+    pub fn set_allowed_uri_schemes(&self, schemes: impl IntoIterator<Item = impl Into<String>>) {
+        self.plugins().allowed_uri_schemes = schemes.into_iter().map(Into::into).collect();
+    }
+
+    /// Sets the URI schemes which a specific plugin may load images from, overriding
+    /// the schemes set with [`Self::set_allowed_uri_schemes`].
+    pub fn set_plugin_allowed_uri_schemes(
+        &self,
+        plugin: PluginId,
+        schemes: impl IntoIterator<Item = impl Into<String>>,
+    ) {
+        self.plugins()
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .allowed_uri_schemes = Some(schemes.into_iter().map(Into::into).collect());
+    }
+
+    /// Gets the URI schemes from which the given plugin may load images.
+    fn allowed_uri_schemes(&self, plugin: PluginId) -> HashSet<String> {
+        let mut plugins = self.plugins();
+        let default_schemes = plugins.allowed_uri_schemes.clone();
+        plugins
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .allowed_uri_schemes
+            .clone()
+            .unwrap_or(default_schemes)
+    }
+
+    /// Resolves a URI that a plugin requested into one that the host's loaders may load.
+    /// Returns an error if the plugin is not permitted to load the URI.
+    fn resolve_uri(
+        ctx: &Context,
+        plugin: PluginId,
+        allowed_schemes: &HashSet<String>,
+        uri: String,
+        source: UriSource,
+    ) -> Result<String, String> {
+        match source {
+            UriSource::Included { bytes } => {
+                // Plugin-provided bytes are namespaced so that they cannot replace
+                // images belonging to the host or other plugins.
+                let uri = format!("bytes://egui_wings/{}/{uri}", plugin.as_raw());
+                if let Some(bytes) = bytes {
+                    ctx.include_bytes(uri.clone(), bytes);
+                }
+                Ok(uri)
+            }
+            UriSource::Host => {
+                let scheme = uri.split_once("://").map(|(scheme, _)| scheme);
+                if scheme.is_some_and(|x| allowed_schemes.contains(x)) {
+                    Ok(uri)
+                } else {
+                    Err(format!("Plugin is not permitted to load {uri:?}"))
+                }
+            }
+        }
+    }
+
+    /// Loads the bytes of a single URI on behalf of a plugin.
+    fn load_uri_bytes(
+        ctx: &Context,
+        plugin: PluginId,
+        allowed_schemes: &HashSet<String>,
+        request: BytesRequest,
+    ) -> BytesResponse {
+        let uri =
+            match Self::resolve_uri(ctx, plugin, allowed_schemes, request.uri, UriSource::Host) {
+                Ok(uri) => uri,
+                Err(error) => return BytesResponse::Failed(error),
+            };
+
+        match ctx.try_load_bytes(&uri) {
+            Ok(load::BytesPoll::Pending { size }) => BytesResponse::Pending { size },
+            Ok(load::BytesPoll::Ready { size, bytes, mime }) => BytesResponse::Ready {
+                size,
+                bytes: bytes.to_vec(),
+                mime,
+            },
+            Err(error) => BytesResponse::Failed(error.to_string()),
+        }
+    }
+
+    /// Decodes a single image on behalf of a plugin.
+    fn load_image(
+        ctx: &Context,
+        plugin: PluginId,
+        allowed_schemes: &HashSet<String>,
+        request: ImageRequest,
+    ) -> ImageResponse {
+        let uri = match Self::resolve_uri(ctx, plugin, allowed_schemes, request.uri, request.source)
+        {
+            Ok(uri) => uri,
+            Err(error) => return ImageResponse::Failed(error),
+        };
+
+        match ctx.try_load_image(&uri, request.size_hint.into()) {
+            Ok(load::ImagePoll::Pending { size }) => ImageResponse::Pending { size },
+            Ok(load::ImagePoll::Ready { image }) => ImageResponse::Ready((*image).clone()),
+            Err(error) => ImageResponse::Failed(error.to_string()),
+        }
+    }
+
+    /// Loads a single texture on behalf of a plugin.
+    fn load_texture(
+        ctx: &Context,
+        plugin: PluginId,
+        allowed_schemes: &HashSet<String>,
+        request: TextureRequest,
+    ) -> TextureResponse {
+        let uri = match Self::resolve_uri(ctx, plugin, allowed_schemes, request.uri, request.source)
+        {
+            Ok(uri) => uri,
+            Err(error) => return TextureResponse::Failed(error),
+        };
+
+        match ctx.try_load_texture(&uri, request.texture_options, request.size_hint.into()) {
+            Ok(load::TexturePoll::Pending { size }) => TextureResponse::Pending { size },
+            Ok(load::TexturePoll::Ready { texture }) => TextureResponse::Ready {
+                id: texture.id,
+                size: texture.size,
+            },
+            Err(error) => TextureResponse::Failed(error.to_string()),
+        }
+    }
+
+    /// Sets which of the shapes painted by the host and by other plugins are sent to plugins.
+    pub fn set_graphics_policy(&self, policy: GraphicsPolicy) {
+        self.plugins().graphics_policy = policy;
+    }
+
+    /// Sets which of the shapes painted by the host and by other plugins are sent to a specific
+    /// plugin, overriding the policy set with [`Self::set_graphics_policy`].
+    pub fn set_plugin_graphics_policy(&self, plugin: PluginId, policy: GraphicsPolicy) {
+        self.plugins()
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .graphics_policy = Some(policy);
+    }
+
+    /// Sets which of the host's input is delivered to plugins.
+    pub fn set_input_policy(&self, policy: InputPolicy) {
+        self.plugins().input_policy = policy;
+    }
+
+    /// Sets which of the host's input is delivered to a specific plugin, overriding
+    /// the policy set with [`Self::set_input_policy`].
+    pub fn set_plugin_input_policy(&self, plugin: PluginId, policy: InputPolicy) {
+        self.plugins()
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .input_policy = Some(policy);
+    }
+
+    /// Sets how the host's input is sent to plugins at the beginning of each transaction.
+    /// [`InputSyncMode::Raw`] reduces the amount of data sent to plugins which build their
+    /// UI every pass.
+    pub fn set_input_sync_mode(&self, mode: InputSyncMode) {
+        self.plugins().input_sync_mode = mode;
+    }
+
+    /// Sets a filter which decides whether keyboard events (such as global shortcuts) should be
+    /// delivered to a plugin with the [`InputPolicy::Routed`] policy when it does not have focus.
+    pub fn set_shortcut_filter(
+        &self,
+        filter: impl Fn(PluginId, &Event) -> bool + Send + Sync + 'static,
+    ) {
+        self.plugins().shortcut_filter = Some(Arc::new(filter));
+    }
+
+    /// Sets whether files dragged onto the host are delivered to plugins. Regardless of
+    /// policy, plugins never observe the paths or contents of dragged files directly.
+    pub fn set_file_drop_policy(&self, policy: FileDropPolicy) {
+        self.plugins().file_drop_policy = policy;
+    }
+
+    /// Sets whether files dragged onto the host are delivered to a specific plugin,
+    /// overriding the policy set with [`Self::set_file_drop_policy`].
+    pub fn set_plugin_file_drop_policy(&self, plugin: PluginId, policy: FileDropPolicy) {
+        self.plugins()
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .file_drop_policy = Some(policy);
+    }
+
+    /// Sets the maximum time that plugins may spend building their UI during a transaction.
+    /// The edits of a plugin which exceeds its budget are discarded, and the last frame that
+    /// it completed within budget is shown instead. A plugin which is still running when its
+    /// budget runs out is stopped with the handler given to [`Self::set_interrupt_handler`].
+    /// By default, plugins have no budget.
+    pub fn set_time_budget(&self, budget: Option<Duration>) {
+        self.plugins().time_budget = budget;
+    }
+
+    /// Sets the time budget for a specific plugin, overriding the budget set with
+    /// [`Self::set_time_budget`]. If `None` is provided, the plugin has no budget.
+    pub fn set_plugin_time_budget(&self, plugin: PluginId, budget: Option<Duration>) {
+        self.plugins()
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .time_budget = Some(budget);
+    }
+
+    /// Sets the function that stops a plugin which is still building its UI when its time
+    /// budget runs out. The function is called from a separate thread with the plugin that
+    /// exceeded its budget, and should make the WASM runtime trap, such as by incrementing
+    /// the engine's epoch. Once the runtime reports the trap, the host should call
+    /// [`Self::recover_interrupted_plugins`].
+    pub fn set_interrupt_handler(&self, handler: impl Fn(PluginId) + Send + Sync + 'static) {
+        self.watchdog.set_handler(Arc::new(handler));
+    }
+
+    /// Abandons the transactions of any plugins which were interrupted before finishing
+    /// them, such as by the WASM runtime trapping or by the interrupt handler. The last frame
+    /// that each plugin completed within budget is shown instead. This should be called after
+    /// the runtime reports an error, and before the host frame ends.
+    pub fn recover_interrupted_plugins(&self) {
+        let interrupted = self.watchdog.take_interrupted();
+        let mut last_frames = Vec::new();
+        for (plugin, state) in &mut self.plugins().plugins {
+            for (name, context) in &mut state.contexts {
+                if let Some(transaction) = context.transaction.take() {
+                    let over_budget = interrupted.contains(plugin);
+                    state.metrics.record(TransactionMetrics {
+                        edit_time: transaction.started.elapsed(),
+                        rejected_outputs: !over_budget as u64,
+                        over_budget: over_budget as u64,
+                        ..Default::default()
+                    });
+                    last_frames.push((name.clone(), context.last_frame.clone()));
+                }
+            }
+        }
+
+        for (name, last_frame) in last_frames {
+            if let Some(ctx) = self.context_named(&name) {
+                last_frame.restore(ctx);
+            }
+        }
+    }
+
+    /// Creates the snapshot that a plugin receives at the beginning of a transaction,
+    /// withholding any input, shapes, selected text and clipboard contents that the plugin
+    /// should not observe.
+    fn plugin_snapshot(
+        ctx: &Context,
+        deltas: ContextSnapshotDeltas,
+        redaction: Option<InputRedaction>,
+        mode: InputSyncMode,
+        owned_layers: HashSet<LayerId>,
+        visible_layers: Option<HashSet<LayerId>>,
+    ) -> CreateContextSnapshot {
+        CreateContextSnapshot::Filtered(
+            ctx.clone(),
+            deltas,
+            SnapshotFilter {
+                layers: visible_layers,
+                owned_layers: Some(owned_layers),
+                redaction,
+                replay_input: mode == InputSyncMode::Raw,
+            },
+        )
+    }
+
+    /// Gets the layers of the named context which are owned by a plugin, along with the
+    /// layers whose shapes are sent to it, or `None` if it receives the shapes of every layer.
+    fn plugin_layers(
+        &self,
+        plugin: PluginId,
+        name: &str,
+    ) -> (HashSet<LayerId>, Option<HashSet<LayerId>>) {
+        let mut plugins = self.plugins();
+        let default_policy = plugins.graphics_policy;
+        let policy = plugins
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .graphics_policy
+            .unwrap_or(default_policy);
+        let owned_layers = plugins.owned_layers(name, plugin);
+        let visible_layers = (policy == GraphicsPolicy::Owned).then(|| owned_layers.clone());
+        (owned_layers, visible_layers)
+    }
+
+    /// Assigns handles to the files which were dropped onto a plugin during the current pass,
+    /// so that the plugin may read them without learning their paths.
+    fn plugin_dropped_files(
+        &self,
+        plugin: PluginId,
+        name: &str,
+        ctx: &Context,
+        redaction: Option<&InputRedaction>,
+    ) -> Vec<PluginDroppedFile> {
+        let Some(redaction) = redaction else {
+            return Vec::new();
+        };
+
+        let pass_nr = ctx.cumulative_pass_nr();
+        let captured = self
+            .plugins()
+            .dropped_files
+            .get(name)
+            .filter(|(pass, _)| *pass == pass_nr)
+            .map(|(_, files)| files.clone());
+        let captured = captured.unwrap_or_else(|| {
+            let files = redaction
+                .viewports
+                .keys()
+                .flat_map(|id| {
+                    ctx.input_for(*id, |x| x.raw.dropped_files.clone())
+                        .into_iter()
+                        .map(|file| (*id, file))
+                })
+                .collect::<Vec<_>>();
+            self.plugins()
+                .dropped_files
+                .insert(name.to_owned(), (pass_nr, files.clone()));
+            files
+        });
+
+        let files = captured
+            .into_iter()
+            .filter(|(id, _)| redaction.viewports.get(id).is_some_and(|x| x.receive_files))
+            .collect::<Vec<_>>();
+
+        if files.is_empty() {
+            return Vec::new();
+        }
+
+        let mut plugins = self.plugins();
+        let mut handles = HashMap::new();
+        let mut result = Vec::new();
+        for (viewport, file) in files {
+            plugins.next_file_handle += 1;
+            let handle = FileHandle::from_raw(plugins.next_file_handle);
+            result.push(PluginDroppedFile::new(handle, viewport, &file));
+            handles.insert(handle, file);
+        }
+
+        plugins.plugins.entry(plugin).or_default().dropped_files = handles;
+        result
+    }
+
+    /// Determines which input of the named context should be withheld from a plugin during
+    /// the current pass, or returns `None` if the plugin observes all input.
+    fn plugin_redaction(
+        &self,
+        plugin: PluginId,
+        name: &str,
+        ctx: &Context,
+    ) -> Option<InputRedaction> {
+        let (policy, file_drop_policy, owned_layers, shortcut_filter) = {
+            let mut plugins = self.plugins();
+            let default_policy = plugins.input_policy;
+            let default_file_drop_policy = plugins.file_drop_policy;
+            let state = plugins.plugins.entry(plugin).or_default();
+            let policy = state.input_policy.unwrap_or(default_policy);
+            let file_drop_policy = state.file_drop_policy.unwrap_or(default_file_drop_policy);
+
+            if policy == InputPolicy::Shared && !InputRedaction::has_files(ctx) {
+                return None;
+            }
+
+            (
+                policy,
+                file_drop_policy,
+                plugins.owned_layers(name, plugin),
+                plugins.shortcut_filter.clone(),
+            )
+        };
+
+        let owns_layer = |layer| owned_layers.contains(&layer);
+        let mut redaction = match policy {
+            InputPolicy::Shared => InputRedaction::for_files(ctx, owns_layer),
+            InputPolicy::Routed => InputRedaction::for_plugin(ctx, owns_layer, |event| {
+                shortcut_filter.as_ref().is_some_and(|x| x(plugin, event))
+            }),
+        };
+
+        if file_drop_policy == FileDropPolicy::Denied {
+            redaction.deny_files();
+        }
+
+        Some(redaction)
+    }
+
+    /// Begins a frame in which the edits of plugins are deferred. Until
+    /// [`Self::end_deferred_frame`] is called, every plugin receives the host state from the
+    /// beginning of the frame, and plugin edits are staged rather than applied. Plugins still
+    /// build their UI one at a time, but none of them observes the edits of the others.
+    pub fn begin_deferred_frame(&self) {
+        self.plugins().staged.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Ends a frame begun with [`Self::begin_deferred_frame`], merging the staged edits of
+    /// each plugin into the host context. Edits are merged in order of plugin identifier, so
+    /// the result does not depend upon the order in which plugins ran, and the edits of a
+    /// plugin which performed several transactions on one context are merged in the order
+    /// that they ended. Shapes, widgets and outputs from all transactions are combined; if
+    /// multiple plugins change the focused widget or open popup, the change merged last is kept.
+    pub fn end_deferred_frame(&self) {
+        let staged = self.plugins().staged.take().unwrap_or_default();
+        for ((plugin, name), edits) in staged {
+            if let Some(ctx) = self.context_named(&name) {
+                for edit in edits {
+                    self.commit_edit(plugin, &name, ctx, edit);
+                }
+            }
+        }
+    }
+
+    /// Applies a plugin's edits to the named host context. If the plugin built its UI during
+    /// a deferred frame, its edits are merged with those of other plugins instead.
+    fn commit_edit(&self, plugin: PluginId, name: &str, ctx: &Context, edit: StagedEdit) {
+        let StagedEdit {
+            edit_time,
+            end,
+            transaction,
+        } = edit;
+        let bytes_in = end.snapshot.encoded_len() as u64;
+        let Some(snapshot) = end.snapshot.decode() else {
+            self.plugins()
+                .plugins
+                .entry(plugin)
+                .or_default()
+                .metrics
+                .record(TransactionMetrics {
+                    bytes_in,
+                    rejected_outputs: 1,
+                    ..Default::default()
+                });
+            return;
+        };
+
+        let start = Instant::now();
+        let owned_layers = self.plugins().owned_layers(name, plugin);
+        match &transaction.base {
+            Some(base) => snapshot.merge(ctx, base, |x| owned_layers.contains(&x)),
+            None => snapshot.apply_from_plugin(
+                ctx,
+                |x| owned_layers.contains(&x),
+                transaction.visible_layers.as_ref(),
+                transaction.redacted,
+            ),
+        }
+        for (viewport, delay) in &end.repaint {
+            ctx.request_repaint_after_for(*delay, *viewport);
+        }
+        let end_time = start.elapsed();
+        let layers = context_layer_ids(ctx);
+
+        let (owned_layers, retained) = {
+            let mut plugins = self.plugins();
+            plugins
+                .commands
+                .extend(end.commands.into_iter().map(|command| ReceivedCommand {
+                    plugin,
+                    context: name.to_owned(),
+                    command,
+                }));
+            for layer in layers.difference(&transaction.layers) {
+                plugins
+                    .layer_owners
+                    .entry((name.to_owned(), *layer))
+                    .or_insert(plugin);
+            }
+
+            let owned_layers = plugins.owned_layers(name, plugin);
+            let state = plugins.plugins.entry(plugin).or_default();
+            state.metrics.record(TransactionMetrics {
+                bytes_in,
+                edit_time,
+                end_time,
+                ..Default::default()
+            });
+            let context = state.context(name);
+            context.repaint_at = end.repaint.values().min().map(|x| Instant::now() + *x);
+
+            (owned_layers, context.retained)
+        };
+
+        let widgets = self.plugins().widgets.clone();
+        let widget_responses = end
+            .widgets
+            .iter()
+            .filter(|x| owned_layers.contains(&x.layer))
+            .filter_map(|placement| {
+                let widget = widgets.get(&placement.name)?;
+                Some((placement.id, widget(ctx, placement)))
+            })
+            .collect::<Vec<_>>();
+        self.plugins()
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .context(name)
+            .widget_responses
+            .extend(widget_responses);
+
+        if transaction.budget.is_some() || retained {
+            let last_frame = LayerGraphics::capture(ctx, &owned_layers);
+            let committed = CommittedFrame {
+                deltas: ContextSnapshotDeltas::from_context(ctx),
+                frame_nr: transaction.deltas.frame_count(),
+                routing: self.plugin_redaction(plugin, name, ctx).unwrap_or_default(),
+            };
+
+            let mut plugins = self.plugins();
+            let context = plugins.plugins.entry(plugin).or_default().context(name);
+            context.last_frame = last_frame;
+            context.committed = Some(committed);
+        }
+    }
+
+    /// Queues batches of events for delivery to the context and viewport in which a widget was added.
+    fn queue_widget_events(&self, widget: &PluginWidget, passes: Vec<Vec<Event>>) {
+        if let Some(ctx) = self.context_named(&widget.context) {
+            automation::queue_events(ctx, widget.viewport_id, passes);
+        }
+    }
+
+    /// Locks the plugin registry.
+    fn plugins(&self) -> MutexGuard<'_, PluginRegistry> {
+        self.plugins
+            .lock()
+            .expect("Failed to lock plugin registry.")
+    }
 }
 
 impl AsMut<dyn Egui> for EguiHost {
@@ -35,19 +824,1096 @@ impl AsMut<dyn Egui> for EguiHost {
 }
 
 impl Egui for EguiHost {
-    fn begin_context_edit(&self, deltas: ContextSnapshotDeltas) -> CreateContextSnapshot {
-        CreateContextSnapshot::FromContext(self.ctx.clone(), deltas)
+    fn register_plugin(&self) -> PluginId {
+        let mut plugins = self.plugins();
+        plugins.next_id += 1;
+        let id = PluginId::from_raw(plugins.next_id);
+        plugins.plugins.insert(id, PluginState::default());
+        id
     }
 
-    fn end_context_edit(&self, state: CreateContextSnapshot) {
-        state.apply(&self.ctx);
+    fn restore_plugin(&self, plugin: PluginId, key: String) -> Option<String> {
+        let mut plugins = self.plugins();
+        let claimed = plugins
+            .plugins
+            .iter()
+            .any(|(id, state)| *id != plugin && state.key.as_ref() == Some(&key));
+        if claimed {
+            return None;
+        }
+
+        let persisted = plugins.persisted.get(&key).cloned();
+        plugins.plugins.entry(plugin).or_default().key = Some(key);
+
+        let persisted = persisted?;
+        for (_, layer, _) in &persisted.areas {
+            plugins
+                .layer_owners
+                .entry((String::new(), *layer))
+                .or_insert(plugin);
+        }
+        drop(plugins);
+
+        persisted.restore_areas(&self.ctx);
+        persisted.memory
+    }
+
+    fn save_plugin_memory(&self, plugin: PluginId, memory: String) {
+        self.plugins().plugins.entry(plugin).or_default().memory = Some(memory);
+    }
+
+    fn load_bytes(
+        &self,
+        plugin: PluginId,
+        context: String,
+        requests: Vec<BytesRequest>,
+    ) -> Vec<BytesResponse> {
+        let Some(ctx) = self.context_named(&context) else {
+            return requests
+                .iter()
+                .map(|_| BytesResponse::Failed(format!("No context named {context:?}")))
+                .collect();
+        };
+
+        let allowed_schemes = self.allowed_uri_schemes(plugin);
+        requests
+            .into_iter()
+            .map(|request| Self::load_uri_bytes(ctx, plugin, &allowed_schemes, request))
+            .collect()
+    }
+
+    fn load_images(
+        &self,
+        plugin: PluginId,
+        context: String,
+        requests: Vec<ImageRequest>,
+    ) -> Vec<ImageResponse> {
+        let Some(ctx) = self.context_named(&context) else {
+            return requests
+                .iter()
+                .map(|_| ImageResponse::Failed(format!("No context named {context:?}")))
+                .collect();
+        };
+
+        let allowed_schemes = self.allowed_uri_schemes(plugin);
+        requests
+            .into_iter()
+            .map(|request| Self::load_image(ctx, plugin, &allowed_schemes, request))
+            .collect()
+    }
+
+    fn load_textures(
+        &self,
+        plugin: PluginId,
+        context: String,
+        requests: Vec<TextureRequest>,
+    ) -> Vec<TextureResponse> {
+        let Some(ctx) = self.context_named(&context) else {
+            return requests
+                .iter()
+                .map(|_| TextureResponse::Failed(format!("No context named {context:?}")))
+                .collect();
+        };
+
+        let allowed_schemes = self.allowed_uri_schemes(plugin);
+        requests
+            .into_iter()
+            .map(|request| Self::load_texture(ctx, plugin, &allowed_schemes, request))
+            .collect()
+    }
+
+    fn load_dropped_file(&self, plugin: PluginId, file: FileHandle) -> Option<Vec<u8>> {
+        let file = self
+            .plugins()
+            .plugins
+            .get(&plugin)?
+            .dropped_files
+            .get(&file)?
+            .clone();
+
+        match (file.bytes, file.path) {
+            (Some(bytes), _) => Some(bytes.to_vec()),
+            (None, Some(path)) => std::fs::read(path).ok(),
+            (None, None) => None,
+        }
+    }
+
+    fn begin_context_edit(
+        &self,
+        plugin: PluginId,
+        context: String,
+        deltas: ContextSnapshotDeltas,
+    ) -> Option<ContextEditStart> {
+        let start = Instant::now();
+        let ctx = self.context_named(&context)?;
+        let host_deltas = ContextSnapshotDeltas::from_context(ctx);
+        let layers = context_layer_ids(ctx);
+        let redaction = self.plugin_redaction(plugin, &context, ctx);
+        let dropped_files = self.plugin_dropped_files(plugin, &context, ctx, redaction.as_ref());
+        let redacted = redaction.is_some();
+        let (owned_layers, visible_layers) = self.plugin_layers(plugin, &context);
+        let input_sync_mode = self.plugins().input_sync_mode;
+        let snapshot = EncodedSnapshot::encode(&Self::plugin_snapshot(
+            ctx,
+            deltas,
+            redaction,
+            input_sync_mode,
+            owned_layers,
+            visible_layers.clone(),
+        ));
+        let bytes_out = snapshot.encoded_len() as u64;
+        let frame_nr = ctx.cumulative_frame_nr();
+
+        let mut plugins = self.plugins();
+        let base = plugins
+            .staged
+            .is_some()
+            .then(|| ContextBase::capture(ctx, visible_layers.as_ref()));
+        plugins.frame_nr = frame_nr;
+        let default_budget = plugins.time_budget;
+        let state = plugins.plugins.entry(plugin).or_default();
+        let budget = state.time_budget.unwrap_or(default_budget);
+        state.metrics.advance_to(frame_nr);
+        state.metrics.record(TransactionMetrics {
+            transactions: 1,
+            bytes_out,
+            begin_time: start.elapsed(),
+            full_resyncs: deltas.is_full_resync() as u64,
+            ..Default::default()
+        });
+        // Persisted memory belongs to the default context.
+        let save_memory = context.is_empty() && std::mem::take(&mut state.save_requested);
+        let started = Instant::now();
+        self.watchdog
+            .set_deadline(plugin, budget.map(|budget| started + budget));
+        state.context(&context).transaction = Some(OpenTransaction {
+            base,
+            budget,
+            started,
+            deltas: host_deltas,
+            layers,
+            visible_layers,
+            redacted,
+        });
+
+        let widget_responses = std::mem::take(&mut state.context(&context).widget_responses)
+            .into_iter()
+            .collect();
+
+        Some(ContextEditStart {
+            dropped_files,
+            snapshot,
+            save_memory,
+            widget_responses,
+        })
+    }
+
+    fn end_context_edit(&self, plugin: PluginId, context: String, end: ContextEditEnd) {
+        self.watchdog.set_deadline(plugin, None);
+        let transaction = self
+            .plugins()
+            .plugins
+            .entry(plugin)
+            .or_default()
+            .context(&context)
+            .transaction
+            .take();
+        let ctx = self.context_named(&context);
+
+        let (Some(transaction), Some(ctx)) = (transaction, ctx) else {
+            self.plugins()
+                .plugins
+                .entry(plugin)
+                .or_default()
+                .metrics
+                .record(TransactionMetrics {
+                    rejected_outputs: 1,
+                    ..Default::default()
+                });
+            return;
+        };
+
+        let edit_time = transaction.started.elapsed();
+        if transaction.budget.is_some_and(|x| x < edit_time) {
+            let last_frame = {
+                let mut plugins = self.plugins();
+                let state = plugins.plugins.entry(plugin).or_default();
+                state.metrics.record(TransactionMetrics {
+                    edit_time,
+                    over_budget: 1,
+                    ..Default::default()
+                });
+                state.context(&context).last_frame.clone()
+            };
+
+            last_frame.restore(ctx);
+            return;
+        }
+
+        let edit = StagedEdit {
+            edit_time,
+            end,
+            transaction,
+        };
+
+        let mut plugins = self.plugins();
+        if let Some(staged) = &mut plugins.staged {
+            staged.entry((plugin, context)).or_default().push(edit);
+            return;
+        }
+        drop(plugins);
+
+        self.commit_edit(plugin, &context, ctx, edit);
+    }
+
+    fn reuse_frame(&self, plugin: PluginId, context: String) -> bool {
+        let Some(ctx) = self.context_named(&context) else {
+            return false;
+        };
+        let host_deltas = ContextSnapshotDeltas::from_context(ctx);
+        let frame_nr = ctx.cumulative_frame_nr();
+        let last_frame = {
+            let mut plugins = self.plugins();
+            plugins.frame_nr = frame_nr;
+            let state = plugins.plugins.entry(plugin).or_default().context(&context);
+            state.retained = true;
+
+            let unchanged = state.committed.as_ref().is_some_and(|x| {
+                x.frame_nr < host_deltas.frame_count() && x.deltas.same_resources(&host_deltas)
+            }) && state.repaint_at.is_none_or(|x| Instant::now() < x);
+
+            if !unchanged {
+                return false;
+            }
+
+            state.last_frame.clone()
+        };
+
+        let routing = self
+            .plugin_redaction(plugin, &context, ctx)
+            .unwrap_or_default();
+        let idle = !routing.has_events(ctx) && last_frame.matches_screens(ctx);
+
+        let mut plugins = self.plugins();
+        let state = plugins.plugins.entry(plugin).or_default();
+        let Some(committed) = state
+            .context(&context)
+            .committed
+            .as_mut()
+            .filter(|x| idle && x.routing.same_routing(&routing))
+        else {
+            return false;
+        };
+
+        committed.frame_nr = host_deltas.frame_count();
+        state.metrics.advance_to(frame_nr);
+        state.metrics.record(TransactionMetrics {
+            reused_frames: 1,
+            ..Default::default()
+        });
+        drop(plugins);
+
+        last_frame.restore(ctx);
+        true
+    }
+
+    fn query_input(&self, plugin: PluginId, context: String) -> Option<HostInput> {
+        let ctx = self.context_named(&context)?;
+        let redaction = self.plugin_redaction(plugin, &context, ctx);
+        Some(HostInput::capture(ctx, redaction.as_ref()))
+    }
+
+    fn paint_layer(&self, plugin: PluginId, context: String, paint: LayerPaint) {
+        let Some(ctx) = self.context_named(&context) else {
+            return;
+        };
+
+        let existing = context_layer_ids(ctx).contains(&paint.layer);
+        match self.plugins().layer_owners.entry((context, paint.layer)) {
+            hash_map::Entry::Occupied(x) if *x.get() != plugin => return,
+            hash_map::Entry::Vacant(x) if !existing => {
+                x.insert(plugin);
+            }
+            _ => {}
+        }
+
+        paint.paint(ctx);
     }
 }
 
 impl GeeseSystem for EguiHost {
     fn new(_: GeeseContextHandle<Self>) -> Self {
         Self {
+            contexts: HashMap::new(),
             ctx: Context::default(),
+            plugins: Mutex::default(),
+            watchdog: Watchdog::default(),
+        }
+    }
+}
+
+/// Tracks the host-side state of all plugins.
+#[derive(Default)]
+struct PluginRegistry {
+    /// The URI schemes from which plugins may load images by default.
+    allowed_uri_schemes: HashSet<String>,
+    /// The commands that plugins have sent which the host has not yet taken.
+    commands: Vec<ReceivedCommand>,
+    /// The files dropped onto each context, keyed by context name, along with the pass
+    /// during which they were captured. Files are captured once per pass, so that every
+    /// plugin receives the files that the host received.
+    dropped_files: HashMap<String, (u64, Vec<(ViewportId, DroppedFile)>)>,
+    /// The policy for delivering dragged files to plugins without an override.
+    file_drop_policy: FileDropPolicy,
+    /// The most recent host frame during which a transaction began.
+    frame_nr: u64,
+    /// The graphics policy for plugins without an override.
+    graphics_policy: GraphicsPolicy,
+    /// The input policy for plugins without an override.
+    input_policy: InputPolicy,
+    /// How the host's input is sent to plugins.
+    input_sync_mode: InputSyncMode,
+    /// The plugin which first drew each layer, keyed by context name and layer.
+    layer_owners: HashMap<(String, LayerId), PluginId>,
+    /// The raw value of the most recently assigned file handle.
+    next_file_handle: u64,
+    /// The raw value of the most recently assigned plugin identifier.
+    next_id: u64,
+    /// The persisted state of each plugin, keyed by persistence key.
+    persisted: BTreeMap<String, PersistedPluginState>,
+    /// The state associated with each plugin.
+    plugins: HashMap<PluginId, PluginState>,
+    /// Decides which keyboard events are delivered to unfocused plugins.
+    shortcut_filter: Option<ShortcutFilter>,
+    /// The edits which will be merged at the end of the current deferred frame, if any,
+    /// keyed by plugin and context name, in the order that they ended.
+    staged: Option<BTreeMap<(PluginId, String), Vec<StagedEdit>>>,
+    /// The time budget for plugins without an override.
+    time_budget: Option<Duration>,
+    /// The widget implementations that plugins may place, keyed by name.
+    widgets: HashMap<String, HostWidgetFn>,
+}
+
+impl PluginRegistry {
+    /// Gets the layers of the named context which are owned by the given plugin.
+    fn owned_layers(&self, context: &str, plugin: PluginId) -> HashSet<LayerId> {
+        self.layer_owners
+            .iter()
+            .filter(|((name, _), owner)| name == context && **owner == plugin)
+            .map(|((_, layer), _)| *layer)
+            .collect()
+    }
+}
+
+/// The host-side state of a single plugin.
+#[derive(Default)]
+struct PluginState {
+    /// The URI schemes from which this plugin may load images, if overridden by the host.
+    allowed_uri_schemes: Option<HashSet<String>>,
+    /// The state of the plugin in each context, keyed by context name.
+    contexts: HashMap<String, PluginContextState>,
+    /// The files most recently dropped onto the plugin, keyed by the handles given to it.
+    dropped_files: HashMap<FileHandle, DroppedFile>,
+    /// The policy for delivering dragged files to this plugin, if overridden by the host.
+    file_drop_policy: Option<FileDropPolicy>,
+    /// The graphics policy for this plugin, if overridden by the host.
+    graphics_policy: Option<GraphicsPolicy>,
+    /// The input policy for this plugin, if overridden by the host.
+    input_policy: Option<InputPolicy>,
+    /// The key under which the plugin's state is persisted.
+    key: Option<String>,
+    /// The most recent persisted memory reported by the plugin.
+    memory: Option<String>,
+    /// The transaction counters for this plugin.
+    metrics: PluginMetrics,
+    /// Whether the plugin should report its persisted memory during its next transaction.
+    save_requested: bool,
+    /// The time budget for this plugin, if overridden by the host.
+    time_budget: Option<Option<Duration>>,
+}
+
+impl PluginState {
+    /// Gets the state of the plugin in the named context.
+    fn context(&mut self, name: &str) -> &mut PluginContextState {
+        self.contexts.entry(name.to_owned()).or_default()
+    }
+}
+
+/// The host-side state of a single plugin with respect to one context.
+#[derive(Default)]
+struct PluginContextState {
+    /// Describes the host state when the plugin's last frame was committed.
+    committed: Option<CommittedFrame>,
+    /// The shapes that the plugin painted during its last transaction within budget.
+    last_frame: LayerGraphics,
+    /// The time at which the plugin requested to be repainted, if any.
+    repaint_at: Option<Instant>,
+    /// Whether the plugin allows its last frame to be reused when nothing relevant has changed.
+    retained: bool,
+    /// The transaction that the plugin is currently performing, if any.
+    transaction: Option<OpenTransaction>,
+    /// The responses of the host widgets that the plugin placed, which have not yet been
+    /// delivered to the plugin.
+    widget_responses: IdMap<HostWidgetResponse>,
+}
+
+/// Describes the host state when a plugin's frame was committed, so that
+/// the frame may be reused if nothing relevant to the plugin changes.
+struct CommittedFrame {
+    /// The host deltas after the frame was committed.
+    deltas: ContextSnapshotDeltas,
+    /// The host frame during which the frame was last shown.
+    frame_nr: u64,
+    /// The input which was withheld from the plugin.
+    routing: InputRedaction,
+}
+
+/// Draws a host widget on behalf of a plugin.
+type HostWidgetFn = Arc<dyn Fn(&Context, &HostWidgetPlacement) -> HostWidgetResponse + Send + Sync>;
+
+/// A plugin's finished transaction, which has not yet been applied to the host context.
+struct StagedEdit {
+    /// The time that the plugin spent building its UI.
+    edit_time: Duration,
+    /// The data that the plugin provided at the end of the transaction.
+    end: ContextEditEnd,
+    /// The transaction that the plugin performed.
+    transaction: OpenTransaction,
+}
+
+/// Describes a transaction which has begun but not yet ended.
+struct OpenTransaction {
+    /// The host content at the beginning of the transaction, if the edits will be merged
+    /// with those of other plugins.
+    base: Option<ContextBase>,
+    /// The time that the plugin may spend building its UI.
+    budget: Option<Duration>,
+    /// When the plugin received its snapshot.
+    started: Instant,
+    /// The host deltas at the beginning of the transaction.
+    deltas: ContextSnapshotDeltas,
+    /// The layers that existed at the beginning of the transaction.
+    layers: HashSet<LayerId>,
+    /// The layers whose shapes were sent to the plugin, if the shapes of other layers
+    /// were withheld.
+    visible_layers: Option<HashSet<LayerId>>,
+    /// Whether input was withheld from the plugin, in which case the host keeps its own.
+    redacted: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a host outside of a `geese` context.
+    fn test_host() -> EguiHost {
+        EguiHost {
+            contexts: HashMap::new(),
+            ctx: Context::default(),
+            plugins: Mutex::default(),
+            watchdog: Watchdog::default(),
+        }
+    }
+
+    /// Creates the copy of a host context that a plugin builds its UI with.
+    fn plugin_context() -> Context {
+        let result = Context::default();
+        result.begin_pass(RawInput::default());
+        result
+    }
+
+    /// Performs a transaction on behalf of a plugin, building its UI on its own copy of
+    /// the named host context with `run_ui`.
+    fn run_plugin(
+        host: &EguiHost,
+        plugin: PluginId,
+        ctx: &Context,
+        name: &str,
+        run_ui: impl FnOnce(&Context),
+    ) {
+        run_plugin_with_repaint(host, plugin, ctx, name, ViewportIdMap::default(), run_ui);
+    }
+
+    /// Performs a transaction like [`run_plugin`], in which the plugin requests to be
+    /// repainted after the given delays.
+    fn run_plugin_with_repaint(
+        host: &EguiHost,
+        plugin: PluginId,
+        ctx: &Context,
+        name: &str,
+        repaint: ViewportIdMap<Duration>,
+        run_ui: impl FnOnce(&Context),
+    ) {
+        let start = host
+            .begin_context_edit(plugin, name.to_owned(), ContextSnapshotDeltas::default())
+            .expect("Failed to begin transaction.");
+        start
+            .snapshot
+            .decode()
+            .expect("Failed to decode host snapshot.")
+            .apply(ctx);
+        let initial_deltas = ContextSnapshotDeltas::from_context(ctx);
+        run_ui(ctx);
+        host.end_context_edit(
+            plugin,
+            name.to_owned(),
+            ContextEditEnd {
+                commands: Vec::new(),
+                snapshot: EncodedSnapshot::encode(&CreateContextSnapshot::FromContext(
+                    ctx.clone(),
+                    initial_deltas,
+                )),
+                repaint,
+                widgets: Vec::new(),
+            },
+        );
+    }
+
+    #[test]
+    fn persistence_keys_belong_to_the_first_plugin() {
+        let host = test_host();
+        let layer = LayerId::new(Order::Middle, Id::new("window"));
+        let area = AreaState {
+            pivot_pos: Some(pos2(40.0, 30.0)),
+            pivot: Align2::LEFT_TOP,
+            size: None,
+            interactable: true,
+            last_became_visible_at: None,
+        };
+        host.load_plugin_states(BTreeMap::from([(
+            "owner".to_owned(),
+            PersistedPluginState {
+                areas: vec![(ViewportId::ROOT, layer, area)],
+                memory: Some("memory".to_owned()),
+            },
+        )]));
+
+        let owner = host.register_plugin();
+        let memory = host.restore_plugin(owner, "owner".to_owned());
+        assert_eq!(memory.as_deref(), Some("memory"));
+        assert_eq!(host.layer_owner(layer), Some(owner));
+
+        let other = host.register_plugin();
+        assert_eq!(host.restore_plugin(other, "owner".to_owned()), None);
+        assert_eq!(host.layer_owner(layer), Some(owner));
+        host.save_plugin_memory(other, "other".to_owned());
+
+        let saved = host.save_plugin_states();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved["owner"].memory.as_deref(), Some("memory"));
+        assert_eq!(saved["owner"].areas.len(), 1);
+    }
+
+    /// An image loader which decodes any bytes into a single white pixel.
+    struct PixelLoader;
+
+    impl load::ImageLoader for PixelLoader {
+        fn id(&self) -> &str {
+            load::generate_loader_id!(PixelLoader)
+        }
+
+        fn load(&self, ctx: &Context, uri: &str, _: load::SizeHint) -> load::ImageLoadResult {
+            match ctx.try_load_bytes(uri)? {
+                load::BytesPoll::Pending { size } => Ok(load::ImagePoll::Pending { size }),
+                load::BytesPoll::Ready { .. } => Ok(load::ImagePoll::Ready {
+                    image: Arc::new(ColorImage::new([1, 1], vec![Color32::WHITE])),
+                }),
+            }
+        }
+
+        fn forget(&self, _: &str) {}
+
+        fn forget_all(&self) {}
+
+        fn byte_size(&self) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn included_images_resolve_when_retried() {
+        let host = test_host();
+        host.ctx.add_image_loader(Arc::new(PixelLoader));
+        let plugin = host.register_plugin();
+        let request = |uri: &str, source| ImageRequest {
+            uri: uri.to_owned(),
+            size_hint: load::SizeHint::default().into(),
+            source,
+        };
+
+        let responses = host.load_images(
+            plugin,
+            String::new(),
+            vec![
+                request(
+                    "bytes://icon.png",
+                    UriSource::Included {
+                        bytes: Some(vec![1, 2, 3]),
+                    },
+                ),
+                request("bytes://icon.png", UriSource::Included { bytes: None }),
+                request("bytes://icon.png", UriSource::Host),
+                request("file://icon.png", UriSource::Host),
+            ],
+        );
+        assert!(matches!(
+            responses[..],
+            [
+                ImageResponse::Ready(_),
+                ImageResponse::Ready(_),
+                ImageResponse::Failed(_),
+                ImageResponse::Failed(_)
+            ]
+        ));
+
+        let other = host.register_plugin();
+        let responses = host.load_images(
+            other,
+            String::new(),
+            vec![request(
+                "bytes://icon.png",
+                UriSource::Included { bytes: None },
+            )],
+        );
+        assert!(matches!(responses[..], [ImageResponse::Failed(_)]));
+    }
+
+    #[test]
+    fn looping_plugins_are_interrupted() {
+        let host = test_host();
+        let plugin = host.register_plugin();
+        let interrupted = Arc::new(atomic::AtomicBool::new(false));
+        let handler_interrupted = interrupted.clone();
+        host.set_interrupt_handler(move |x| {
+            assert_eq!(x, plugin);
+            handler_interrupted.store(true, atomic::Ordering::Release);
+        });
+        host.set_plugin_time_budget(plugin, Some(Duration::from_millis(50)));
+
+        // Areas are invisible during their first pass, so the plugin draws twice.
+        let plugin_ctx = plugin_context();
+        for _ in 0..2 {
+            host.ctx.begin_pass(RawInput::default());
+            run_plugin(&host, plugin, &plugin_ctx, "", |ctx| {
+                Area::new(Id::new("plugin")).show(ctx, |ui| ui.label("Within budget"));
+            });
+            let _ = host.ctx.end_pass();
         }
+
+        // The plugin never ends its transaction, like a WASM loop that only stops when the
+        // runtime traps.
+        host.ctx.begin_pass(RawInput::default());
+        host.begin_context_edit(plugin, String::new(), ContextSnapshotDeltas::default())
+            .expect("Failed to begin transaction.");
+        let started = Instant::now();
+        while !interrupted.load(atomic::Ordering::Acquire) {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "The plugin was never interrupted."
+            );
+            std::thread::yield_now();
+        }
+
+        host.recover_interrupted_plugins();
+        assert!(!host.ctx.end_pass().shapes.is_empty());
+        let metrics = &host.metrics().plugins[&plugin].current_frame;
+        assert_eq!(metrics.over_budget, 1);
+        assert_eq!(metrics.rejected_outputs, 0);
+    }
+
+    /// Shows a plugin's area during two host frames, after which the area is visible, and
+    /// requests the given repaint delay during the second frame.
+    fn show_retained_plugin(host: &EguiHost, plugin: PluginId, repaint: Option<Duration>) {
+        let plugin_ctx = plugin_context();
+        let _ = host.ctx.run(RawInput::default(), |_| {
+            assert!(!host.reuse_frame(plugin, String::new()));
+            run_plugin(host, plugin, &plugin_ctx, "", |ctx| {
+                Area::new(Id::new("plugin")).show(ctx, |ui| ui.label("Retained"));
+            });
+        });
+
+        let repaint = repaint
+            .map(|delay| ViewportIdMap::from_iter([(ViewportId::ROOT, delay)]))
+            .unwrap_or_default();
+        let _ = host.ctx.run(RawInput::default(), |_| {
+            run_plugin_with_repaint(host, plugin, &plugin_ctx, "", repaint.clone(), |ctx| {
+                Area::new(Id::new("plugin")).show(ctx, |ui| ui.label("Retained"));
+            });
+        });
+    }
+
+    #[test]
+    fn unchanged_frames_are_reused() {
+        let host = test_host();
+        let plugin = host.register_plugin();
+        show_retained_plugin(&host, plugin, Some(Duration::from_secs(3600)));
+
+        for _ in 0..2 {
+            let output = host.ctx.run(RawInput::default(), |_| {
+                assert!(host.reuse_frame(plugin, String::new()));
+            });
+            assert!(!output.shapes.is_empty());
+        }
+        assert_eq!(host.metrics().plugins[&plugin].total.reused_frames, 2);
+
+        let input = RawInput {
+            events: vec![Event::PointerMoved(pos2(10.0, 10.0))],
+            ..Default::default()
+        };
+        let _ = host.ctx.run(input, |_| {
+            assert!(!host.reuse_frame(plugin, String::new()));
+        });
+    }
+
+    #[test]
+    fn repaint_requests_force_a_rebuild() {
+        let host = test_host();
+        let plugin = host.register_plugin();
+        show_retained_plugin(&host, plugin, Some(Duration::ZERO));
+
+        let output = host.ctx.run(RawInput::default(), |_| {
+            assert!(!host.reuse_frame(plugin, String::new()));
+        });
+        assert!(output.shapes.is_empty());
+        assert_eq!(host.metrics().plugins[&plugin].total.reused_frames, 0);
+    }
+
+    #[test]
+    fn deferred_frames_keep_every_transaction() {
+        let host = test_host();
+        let first = host.register_plugin();
+        let second = host.register_plugin();
+        let first_ctx = plugin_context();
+        let second_ctx = plugin_context();
+        let show_button = |ctx: &Context, label: &str| {
+            Area::new(Id::new(label)).show(ctx, |ui| ui.button(label));
+        };
+
+        for _ in 0..2 {
+            let _ = host.ctx.run(RawInput::default(), |_| {
+                host.begin_deferred_frame();
+                run_plugin(&host, second, &second_ctx, "", |ctx| {
+                    show_button(ctx, "Other")
+                });
+                run_plugin(&host, first, &first_ctx, "", |ctx| show_button(ctx, "Menu"));
+                run_plugin(&host, first, &first_ctx, "", |ctx| {
+                    show_button(ctx, "Popup")
+                });
+                host.end_deferred_frame();
+            });
+        }
+
+        let areas = |plugin| {
+            host.plugin_widgets(plugin)
+                .iter()
+                .map(|x| x.layer_id.id)
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(
+            areas(first),
+            HashSet::from([Id::new("Menu"), Id::new("Popup")])
+        );
+        assert_eq!(areas(second), HashSet::from([Id::new("Other")]));
+        assert_eq!(host.metrics().plugins[&first].last_frame.transactions, 2);
+    }
+
+    #[test]
+    fn plugins_draw_into_named_contexts() {
+        let mut host = test_host();
+        host.register_context("hud", Context::default());
+        let plugin = host.register_plugin();
+        let missing = host.begin_context_edit(
+            plugin,
+            "missing".to_owned(),
+            ContextSnapshotDeltas::default(),
+        );
+        assert!(missing.is_none());
+
+        let layer = |name: &str| LayerId::new(Order::Middle, Id::new(name));
+        for name in ["", "hud"] {
+            let plugin_ctx = plugin_context();
+            let ctx = host
+                .context_named(name)
+                .expect("Context was not registered.");
+            let _ = ctx.run(RawInput::default(), |_| {
+                run_plugin(&host, plugin, &plugin_ctx, name, |ctx| {
+                    Area::new(layer(name).id).show(ctx, |ui| ui.label(name));
+                });
+            });
+        }
+
+        let [main, hud] = [layer(""), layer("hud")];
+        assert_eq!(host.layer_owner(main), Some(plugin));
+        assert_eq!(host.layer_owner(hud), None);
+        assert_eq!(host.layer_owner_in("hud", hud), Some(plugin));
+        assert!(host.ctx.memory(|x| x.area_rect(hud.id)).is_none());
+
+        let removed = host
+            .unregister_context("hud")
+            .expect("Context was not registered.");
+        assert!(removed.memory(|x| x.area_rect(hud.id)).is_some());
+        assert!(host.context_named("hud").is_none());
+    }
+
+    #[test]
+    fn offscreen_pointer_input_is_mapped_from_texture_coordinates() {
+        let mut surface = OffscreenSurface::new([400, 300], 2.0);
+        assert_eq!(
+            surface.screen_rect(),
+            Rect::from_min_size(Pos2::ZERO, vec2(200.0, 150.0))
+        );
+        assert_eq!(surface.uv_to_pos(pos2(0.5, 0.5)), pos2(100.0, 75.0));
+        assert_eq!(surface.uv_to_pos(pos2(1.0, 1.0)), pos2(200.0, 150.0));
+
+        let mut host = test_host();
+        host.register_context("monitor", surface.context().clone());
+        let plugin = host.register_plugin();
+        let plugin_ctx = plugin_context();
+        let clicks = std::cell::Cell::new(0);
+        let run_frame = |surface: &mut OffscreenSurface| {
+            surface.run(|_| {
+                run_plugin(&host, plugin, &plugin_ctx, "monitor", |ctx| {
+                    Area::new(Id::new("monitor"))
+                        .fixed_pos(Pos2::ZERO)
+                        .show(ctx, |ui| {
+                            if ui.button("Monitor button").clicked() {
+                                clicks.set(clicks.get() + 1);
+                            }
+                        });
+                });
+            })
+        };
+
+        let frame = run_frame(&mut surface);
+        assert_eq!(frame.size_in_pixels, [400, 300]);
+        assert_eq!(frame.pixels_per_point, 2.0);
+        run_frame(&mut surface);
+
+        surface.pointer_moved(pos2(0.02, 0.02));
+        run_frame(&mut surface);
+        surface.pointer_button(PointerButton::Primary, true);
+        run_frame(&mut surface);
+        surface.pointer_button(PointerButton::Primary, false);
+        let frame = run_frame(&mut surface);
+        assert!(!frame.primitives.is_empty());
+        assert_eq!(clicks.get(), 1);
+    }
+
+    #[test]
+    fn plugins_only_paint_their_own_layers() {
+        let host = test_host();
+        let first = host.register_plugin();
+        let second = host.register_plugin();
+        let layer = LayerId::new(Order::Foreground, Id::new("overlay"));
+        let paint = |plugin, color| {
+            let square = Rect::from_min_size(Pos2::ZERO, vec2(10.0, 10.0));
+            host.paint_layer(
+                plugin,
+                String::new(),
+                LayerPaint {
+                    layer,
+                    shapes: vec![Shape::rect_filled(square, 0.0, color).into()],
+                },
+            );
+        };
+
+        let output = host.ctx.run(RawInput::default(), |_| {
+            paint(first, Color32::RED);
+            paint(second, Color32::BLUE);
+        });
+        assert_eq!(host.layer_owner(layer), Some(first));
+        let colors = output
+            .shapes
+            .iter()
+            .filter_map(|x| match &x.shape {
+                Shape::Rect(x) => Some(x.fill),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(colors, [Color32::RED]);
+    }
+
+    #[test]
+    fn plugins_do_not_observe_host_widgets() {
+        let host = test_host();
+        let plugin = host.register_plugin();
+        host.set_plugin_graphics_policy(plugin, GraphicsPolicy::Owned);
+        let plugin_ctx = plugin_context();
+        let mut text = String::new();
+        let text_edit = |ctx: &Context, text: &mut String| {
+            Area::new(Id::new("host"))
+                .show(ctx, |ui| {
+                    let response = ui.text_edit_singleline(text);
+                    response.request_focus();
+                    response.id
+                })
+                .inner
+        };
+
+        let mut id = Id::NULL;
+        let _ = host
+            .ctx
+            .run(RawInput::default(), |ctx| id = text_edit(ctx, &mut text));
+        let input = RawInput {
+            events: vec![Event::Text("hunter2".to_owned())],
+            ..Default::default()
+        };
+        let output = host.ctx.run(input, |ctx| {
+            text_edit(ctx, &mut text);
+            run_plugin(&host, plugin, &plugin_ctx, "", |ctx| {
+                assert!(ctx.output(|x| x.events.is_empty()));
+                assert!(ctx.read_response(id).is_none());
+                Area::new(Id::new("plugin")).show(ctx, |ui| ui.button("Plugin"));
+            });
+            assert!(ctx.read_response(id).is_some());
+        });
+
+        assert_eq!(text, "hunter2");
+        let values = output
+            .platform_output
+            .events
+            .iter()
+            .filter_map(|x| x.widget_info().current_text_value.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["hunter2"]);
+    }
+
+    #[test]
+    fn input_queries_withhold_routed_input() {
+        let host = test_host();
+        let shared = host.register_plugin();
+        let routed = host.register_plugin();
+        host.set_plugin_input_policy(routed, InputPolicy::Routed);
+        assert!(host.query_input(shared, "missing".to_owned()).is_none());
+
+        host.ctx.begin_pass(RawInput {
+            events: vec![Event::Key {
+                key: Key::A,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers: Modifiers::SHIFT,
+            }],
+            modifiers: Modifiers::SHIFT,
+            ..Default::default()
+        });
+        let input = |plugin| {
+            host.query_input(plugin, String::new())
+                .expect("Context was not registered.")
+                .input
+        };
+        let shared_input = input(shared);
+        assert!(shared_input.key_pressed(Key::A));
+        assert!(shared_input.modifiers.shift);
+        assert!(!input(routed).key_pressed(Key::A));
+        let _ = host.ctx.end_pass();
+
+        let metrics = host.metrics();
+        for plugin in [shared, routed] {
+            assert_eq!(metrics.plugins[&plugin].total.transactions, 0);
+        }
+    }
+
+    #[test]
+    fn automation_operates_plugin_widgets() {
+        let host = test_host();
+        // Widget labels and types are only recorded while interactive widgets are shown.
+        host.ctx
+            .style_mut(|x| x.debug.show_interactive_widgets = true);
+        let plugin = host.register_plugin();
+        let plugin_ctx = plugin_context();
+        let clicks = std::cell::Cell::new(0);
+        let text = std::cell::RefCell::new(String::new());
+        let value = std::cell::Cell::new(0.0);
+        let run_frames = |count| {
+            for _ in 0..count {
+                let _ = host.ctx.run(RawInput::default(), |_| {
+                    run_plugin(&host, plugin, &plugin_ctx, "", |ctx| {
+                        Area::new(Id::new("form"))
+                            .fixed_pos(Pos2::ZERO)
+                            .show(ctx, |ui| {
+                                if ui.button("Submit").clicked() {
+                                    clicks.set(clicks.get() + 1);
+                                }
+                                ui.text_edit_singleline(&mut *text.borrow_mut());
+                                let mut x = value.get();
+                                ui.add(DragValue::new(&mut x));
+                                value.set(x);
+                            });
+                    });
+                });
+            }
+        };
+        run_frames(2);
+
+        let widgets = host.plugin_widgets(plugin);
+        let widget = |widget_type| {
+            widgets
+                .iter()
+                .find(|x| x.widget_type() == Some(widget_type))
+                .expect("Widget was not found.")
+        };
+        let button = widget(WidgetType::Button);
+        assert_eq!(button.label(), Some("Submit"));
+        assert!(button.enabled);
+
+        host.click_widget(button);
+        run_frames(2);
+        assert_eq!(clicks.get(), 1);
+
+        host.type_into_widget(widget(WidgetType::TextEdit), "typed");
+        run_frames(3);
+        assert_eq!(*text.borrow(), "typed");
+
+        host.drag_widget(widget(WidgetType::DragValue), vec2(20.0, 0.0));
+        run_frames(4);
+        assert!(value.get() > 0.0);
+    }
+
+    #[test]
+    fn metrics_count_transactions_per_frame() {
+        let host = test_host();
+        let plugin = host.register_plugin();
+        let plugin_ctx = plugin_context();
+
+        let _ = host.ctx.run(RawInput::default(), |_| {
+            for _ in 0..2 {
+                run_plugin(&host, plugin, &plugin_ctx, "", |_| {});
+            }
+            host.end_context_edit(
+                plugin,
+                String::new(),
+                ContextEditEnd {
+                    commands: Vec::new(),
+                    snapshot: EncodedSnapshot::encode(&CreateContextSnapshot::FromContext(
+                        plugin_ctx.clone(),
+                        ContextSnapshotDeltas::default(),
+                    )),
+                    repaint: ViewportIdMap::default(),
+                    widgets: Vec::new(),
+                },
+            );
+        });
+
+        let metrics = host.metrics().plugins[&plugin].clone();
+        assert_eq!(metrics.current_frame.transactions, 2);
+        assert_eq!(metrics.current_frame.full_resyncs, 2);
+        assert_eq!(metrics.current_frame.rejected_outputs, 1);
+        assert!(metrics.current_frame.bytes_out > 0);
+        assert!(metrics.current_frame.bytes_in > 0);
+
+        let _ = host.ctx.run(RawInput::default(), |_| {
+            run_plugin(&host, plugin, &plugin_ctx, "", |_| {});
+        });
+
+        let metrics = host.metrics().plugins[&plugin].clone();
+        assert_eq!(metrics.current_frame.transactions, 1);
+        assert_eq!(metrics.last_frame.transactions, 2);
+        assert_eq!(metrics.last_frame.rejected_outputs, 1);
+        assert_eq!(metrics.total.transactions, 3);
+        assert_eq!(
+            metrics.total.bytes_out,
+            metrics.last_frame.bytes_out + metrics.current_frame.bytes_out
+        );
     }
 }
@@ -0,0 +1,92 @@
+use egui_wings::*;
+use std::collections::*;
+use std::ops::*;
+use std::time::*;
+
+/// Counters describing the `egui` transactions performed by plugins.
+#[derive(Clone, Debug, Default)]
+pub struct EguiMetrics {
+    /// The host frame during which these metrics were captured.
+    pub frame_nr: u64,
+    /// The metrics for each plugin that has registered with the host.
+    pub plugins: HashMap<PluginId, PluginMetrics>,
+}
+
+/// Counters describing the `egui` transactions performed by a single plugin.
+#[derive(Clone, Debug, Default)]
+pub struct PluginMetrics {
+    /// The accumulated counters for the lifetime of the plugin.
+    pub total: TransactionMetrics,
+    /// The counters for the most recent completed frame in which the plugin was active.
+    pub last_frame: TransactionMetrics,
+    /// The counters for the frame which is currently in progress.
+    pub current_frame: TransactionMetrics,
+    /// The host frame to which `current_frame` belongs.
+    pub frame_nr: u64,
+}
+
+impl PluginMetrics {
+    /// Moves the current frame counters into `last_frame` if the host has advanced to a new frame.
+    pub(crate) fn advance_to(&mut self, frame_nr: u64) {
+        if self.frame_nr != frame_nr {
+            self.last_frame = std::mem::take(&mut self.current_frame);
+            self.frame_nr = frame_nr;
+        }
+    }
+
+    /// Adds the given counters to both the frame and lifetime totals.
+    pub(crate) fn record(&mut self, metrics: TransactionMetrics) {
+        self.current_frame += metrics;
+        self.total += metrics;
+    }
+}
+
+/// Counters describing a set of `egui` transactions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransactionMetrics {
+    /// The number of transactions that were started.
+    pub transactions: u64,
+    /// The number of encoded snapshot bytes received from the plugin.
+    pub bytes_in: u64,
+    /// The number of encoded snapshot bytes sent to the plugin.
+    pub bytes_out: u64,
+    /// The time spent on the host beginning transactions.
+    pub begin_time: Duration,
+    /// The time between the beginning and end of each transaction, while the plugin built its UI.
+    pub edit_time: Duration,
+    /// The time spent on the host applying the plugin's output.
+    pub end_time: Duration,
+    /// The number of transactions which required the complete context state to be sent.
+    pub full_resyncs: u64,
+    /// The number of transaction outputs that the host refused to apply.
+    pub rejected_outputs: u64,
+    /// The number of transactions which were discarded for exceeding the plugin's time budget.
+    pub over_budget: u64,
+    /// The number of frames in which the plugin's previous frame was shown again, because
+    /// nothing relevant to the plugin had changed.
+    pub reused_frames: u64,
+}
+
+impl Add for TransactionMetrics {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for TransactionMetrics {
+    fn add_assign(&mut self, rhs: Self) {
+        self.transactions += rhs.transactions;
+        self.bytes_in += rhs.bytes_in;
+        self.bytes_out += rhs.bytes_out;
+        self.begin_time += rhs.begin_time;
+        self.edit_time += rhs.edit_time;
+        self.end_time += rhs.end_time;
+        self.full_resyncs += rhs.full_resyncs;
+        self.rejected_outputs += rhs.rejected_outputs;
+        self.over_budget += rhs.over_budget;
+        self.reused_frames += rhs.reused_frames;
+    }
+}
@@ -0,0 +1,166 @@
+use egui_wings::egui::*;
+use std::time::*;
+
+/// A context which is drawn offscreen with a fixed virtual screen size, such as the UI of
+/// an in-game monitor. Plugins draw into the surface once its context is registered with
+/// [`EguiHost::register_context`](crate::EguiHost::register_context). Pointer input is
+/// provided in texture coordinates (for example, from the UV of a ray hit), and is mapped
+/// to the surface's screen before being delivered.
+pub struct OffscreenSurface {
+    /// The context into which plugins draw.
+    ctx: Context,
+    /// The input events which will be delivered during the next frame.
+    events: Vec<Event>,
+    /// The modifier keys which are currently held.
+    modifiers: Modifiers,
+    /// The number of physical pixels for each point.
+    pixels_per_point: f32,
+    /// The most recent position of the pointer, in points.
+    pointer: Option<Pos2>,
+    /// The size of the surface in physical pixels.
+    size_in_pixels: [u32; 2],
+    /// When the surface was created.
+    start: Instant,
+}
+
+impl OffscreenSurface {
+    /// Creates a new surface with the given size in physical pixels.
+    pub fn new(size_in_pixels: [u32; 2], pixels_per_point: f32) -> Self {
+        Self {
+            ctx: Context::default(),
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            pixels_per_point,
+            pointer: None,
+            size_in_pixels,
+            start: Instant::now(),
+        }
+    }
+
+    /// Gets the context into which plugins draw.
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// Gets the size of the surface in physical pixels.
+    pub fn size_in_pixels(&self) -> [u32; 2] {
+        self.size_in_pixels
+    }
+
+    /// Gets the number of physical pixels for each point.
+    pub fn pixels_per_point(&self) -> f32 {
+        self.pixels_per_point
+    }
+
+    /// Changes the size and scale of the surface, taking effect during the next frame.
+    pub fn set_size(&mut self, size_in_pixels: [u32; 2], pixels_per_point: f32) {
+        self.size_in_pixels = size_in_pixels;
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    /// Gets the screen of the surface, in points.
+    pub fn screen_rect(&self) -> Rect {
+        let [width, height] = self.size_in_pixels;
+        Rect::from_min_size(
+            Pos2::ZERO,
+            vec2(width as f32, height as f32) / self.pixels_per_point,
+        )
+    }
+
+    /// Converts texture coordinates, where `(0, 0)` is the top left and `(1, 1)` is the
+    /// bottom right of the surface, to a position on the surface's screen.
+    pub fn uv_to_pos(&self, uv: Pos2) -> Pos2 {
+        self.screen_rect().lerp_inside(uv.to_vec2())
+    }
+
+    /// Sets the modifier keys which are held during subsequent input.
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+
+    /// Moves the pointer to the given texture coordinates.
+    pub fn pointer_moved(&mut self, uv: Pos2) {
+        let pos = self.uv_to_pos(uv);
+        self.pointer = Some(pos);
+        self.events.push(Event::PointerMoved(pos));
+    }
+
+    /// Presses or releases a pointer button at the pointer's current position.
+    /// Does nothing if the pointer is not over the surface.
+    pub fn pointer_button(&mut self, button: PointerButton, pressed: bool) {
+        if let Some(pos) = self.pointer {
+            self.events.push(Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers: self.modifiers,
+            });
+        }
+    }
+
+    /// Scrolls the content under the pointer by the given number of points.
+    pub fn scroll(&mut self, delta: Vec2) {
+        self.events.push(Event::MouseWheel {
+            unit: MouseWheelUnit::Point,
+            delta,
+            modifiers: self.modifiers,
+        });
+    }
+
+    /// Indicates that the pointer is no longer over the surface.
+    pub fn pointer_left(&mut self) {
+        if self.pointer.take().is_some() {
+            self.events.push(Event::PointerGone);
+        }
+    }
+
+    /// Queues an arbitrary input event, such as a key press, for the next frame.
+    /// Pointer positions in the event should already be in the surface's points.
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// Runs a frame of the surface with the input received since the last frame. Plugins
+    /// should draw into the surface's context during `run_ui`, after which the output is
+    /// tessellated so that it may be rendered into a texture.
+    pub fn run(&mut self, run_ui: impl FnMut(&Context)) -> OffscreenFrame {
+        let mut input = RawInput {
+            screen_rect: Some(self.screen_rect()),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: self.pointer.is_some(),
+            ..Default::default()
+        };
+        input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.pixels_per_point);
+
+        let output = self.ctx.run(input, run_ui);
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+
+        OffscreenFrame {
+            pixels_per_point: output.pixels_per_point,
+            platform_output: output.platform_output,
+            primitives,
+            size_in_pixels: self.size_in_pixels,
+            textures_delta: output.textures_delta,
+        }
+    }
+}
+
+/// The output of a single frame of an [`OffscreenSurface`], ready to be rendered.
+pub struct OffscreenFrame {
+    /// The number of physical pixels for each point.
+    pub pixels_per_point: f32,
+    /// The platform output, such as the cursor icon and copied text.
+    pub platform_output: PlatformOutput,
+    /// The tessellated shapes to render.
+    pub primitives: Vec<ClippedPrimitive>,
+    /// The size of the surface in physical pixels.
+    pub size_in_pixels: [u32; 2],
+    /// The changes to textures which must be made before rendering.
+    pub textures_delta: TexturesDelta,
+}
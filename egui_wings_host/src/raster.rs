@@ -0,0 +1,331 @@
+use egui_wings::egui::epaint::*;
+use egui_wings::egui::*;
+use std::collections::*;
+
+/// Renders tessellated `egui` output into images on the CPU, without a GPU. This allows
+/// the frames that plugins produce to be compared against stored images in tests, so
+/// that snapshot and layout bugs which only appear visually may be caught. The renderer
+/// blends premultiplied colors in gamma space, like the `egui` backends, but is not
+/// guaranteed to match them exactly; images should be compared with a small tolerance.
+#[derive(Clone, Default)]
+pub struct SoftwareRenderer {
+    /// The textures which have been uploaded, such as the font atlas.
+    textures: HashMap<TextureId, SoftwareTexture>,
+}
+
+impl SoftwareRenderer {
+    /// Creates a new renderer with no textures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies the texture changes which were produced during a frame. Textures persist
+    /// between frames, so every frame's delta must be applied in order.
+    pub fn update_textures(&mut self, delta: &TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let ImageData::Color(image) = &image_delta.image;
+            match image_delta.pos {
+                None => {
+                    self.textures.insert(
+                        *id,
+                        SoftwareTexture {
+                            image: image.as_ref().clone(),
+                            options: image_delta.options,
+                        },
+                    );
+                }
+                Some([x, y]) => {
+                    let Some(texture) = self.textures.get_mut(id) else {
+                        continue;
+                    };
+                    texture.options = image_delta.options;
+                    let [width, height] = texture.image.size;
+                    for row in 0..image.height().min(height.saturating_sub(y)) {
+                        for column in 0..image.width().min(width.saturating_sub(x)) {
+                            texture.image.pixels[(y + row) * width + x + column] =
+                                image.pixels[row * image.width() + column];
+                        }
+                    }
+                }
+            }
+        }
+
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    /// Renders tessellated primitives into a new image of the given size in physical
+    /// pixels, which is first cleared to the given color. Paint callbacks are skipped.
+    pub fn render(
+        &self,
+        primitives: &[ClippedPrimitive],
+        size_in_pixels: [usize; 2],
+        pixels_per_point: f32,
+        clear_color: Color32,
+    ) -> ColorImage {
+        let mut target = ColorImage::filled(size_in_pixels, clear_color);
+        for primitive in primitives {
+            if let Primitive::Mesh(mesh) = &primitive.primitive {
+                let clip_rect = Rect::from_min_max(
+                    (primitive.clip_rect.min.to_vec2() * pixels_per_point)
+                        .round()
+                        .to_pos2(),
+                    (primitive.clip_rect.max.to_vec2() * pixels_per_point)
+                        .round()
+                        .to_pos2(),
+                );
+                self.draw_mesh(&mut target, mesh, clip_rect, pixels_per_point);
+            }
+        }
+        target
+    }
+
+    /// Applies the texture changes of the given output, then tessellates and renders it.
+    /// This is typically called with the output of the host context after all plugins
+    /// have drawn into it.
+    pub fn render_output(
+        &mut self,
+        ctx: &Context,
+        output: FullOutput,
+        size_in_pixels: [usize; 2],
+        clear_color: Color32,
+    ) -> ColorImage {
+        self.update_textures(&output.textures_delta);
+        let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+        self.render(
+            &primitives,
+            size_in_pixels,
+            output.pixels_per_point,
+            clear_color,
+        )
+    }
+
+    /// Draws the triangles of a mesh into the target, within the clip rectangle in pixels.
+    fn draw_mesh(
+        &self,
+        target: &mut ColorImage,
+        mesh: &Mesh,
+        clip_rect: Rect,
+        pixels_per_point: f32,
+    ) {
+        let Some(texture) = self.textures.get(&mesh.texture_id) else {
+            return;
+        };
+
+        let [width, height] = target.size;
+        let bounds = clip_rect.intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            vec2(width as f32, height as f32),
+        ));
+        if !bounds.is_positive() {
+            return;
+        }
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let vertices = [
+                mesh.vertices[triangle[0] as usize],
+                mesh.vertices[triangle[1] as usize],
+                mesh.vertices[triangle[2] as usize],
+            ];
+            let positions = vertices.map(|x| x.pos * pixels_per_point);
+
+            let area = edge(positions[0], positions[1], positions[2]);
+            if area == 0.0 {
+                continue;
+            }
+
+            let triangle_rect = Rect::from_points(&positions).intersect(bounds);
+            if !triangle_rect.is_positive() {
+                continue;
+            }
+
+            let min_x = triangle_rect.min.x.floor() as usize;
+            let min_y = triangle_rect.min.y.floor() as usize;
+            let max_x = (triangle_rect.max.x.ceil() as usize).min(width);
+            let max_y = (triangle_rect.max.y.ceil() as usize).min(height);
+
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let center = pos2(x as f32 + 0.5, y as f32 + 0.5);
+                    if !bounds.contains(center) {
+                        continue;
+                    }
+
+                    let weights = [
+                        edge(positions[1], positions[2], center) / area,
+                        edge(positions[2], positions[0], center) / area,
+                        edge(positions[0], positions[1], center) / area,
+                    ];
+                    if weights.iter().any(|&w| w < 0.0) {
+                        continue;
+                    }
+
+                    let uv = vertices
+                        .iter()
+                        .zip(weights)
+                        .fold(Vec2::ZERO, |acc, (v, w)| acc + v.uv.to_vec2() * w);
+                    let color = vertices
+                        .iter()
+                        .zip(weights)
+                        .fold(Rgba::TRANSPARENT, |acc, (v, w)| {
+                            acc + gamma_rgba(v.color) * w
+                        });
+
+                    let source = color * texture.sample(uv.to_pos2());
+                    let pixel = &mut target.pixels[y * width + x];
+                    *pixel = blend(source, gamma_rgba(*pixel));
+                }
+            }
+        }
+    }
+}
+
+/// A texture which has been uploaded to a [`SoftwareRenderer`].
+#[derive(Clone)]
+struct SoftwareTexture {
+    /// The premultiplied pixels of the texture.
+    image: ColorImage,
+    /// How the texture is sampled.
+    options: TextureOptions,
+}
+
+impl SoftwareTexture {
+    /// Samples the texture at the given texture coordinates, in gamma space.
+    fn sample(&self, uv: Pos2) -> Rgba {
+        let [width, height] = self.image.size;
+        if width == 0 || height == 0 {
+            return Rgba::TRANSPARENT;
+        }
+
+        let x = uv.x * width as f32 - 0.5;
+        let y = uv.y * height as f32 - 0.5;
+        match self.options.magnification {
+            TextureFilter::Nearest => self.texel(x.round() as isize, y.round() as isize),
+            TextureFilter::Linear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x0 + 1, y0) * tx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+        }
+    }
+
+    /// Gets a single texel in gamma space, applying the texture's wrap mode to
+    /// coordinates outside of the image.
+    fn texel(&self, x: isize, y: isize) -> Rgba {
+        let [width, height] = self.image.size;
+        let x = wrap(x, width, self.options.wrap_mode);
+        let y = wrap(y, height, self.options.wrap_mode);
+        gamma_rgba(self.image.pixels[y * width + x])
+    }
+}
+
+/// Maps a texel coordinate into the range of a texture dimension with the given wrap mode.
+fn wrap(value: isize, size: usize, mode: TextureWrapMode) -> usize {
+    let size = size as isize;
+    let wrapped = match mode {
+        TextureWrapMode::ClampToEdge => value.clamp(0, size - 1),
+        TextureWrapMode::Repeat => value.rem_euclid(size),
+        TextureWrapMode::MirroredRepeat => {
+            let period = value.rem_euclid(2 * size);
+            if period < size {
+                period
+            } else {
+                2 * size - 1 - period
+            }
+        }
+    };
+    wrapped as usize
+}
+
+/// Computes twice the signed area of the triangle `(a, b, c)`.
+fn edge(a: Pos2, b: Pos2, c: Pos2) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// Converts a premultiplied color to normalized components without changing its gamma.
+fn gamma_rgba(color: Color32) -> Rgba {
+    let [r, g, b, a] = color.to_array().map(|x| x as f32 / 255.0);
+    Rgba::from_rgba_premultiplied(r, g, b, a)
+}
+
+/// Blends a premultiplied source color over a destination color.
+fn blend(source: Rgba, destination: Rgba) -> Color32 {
+    let color = source + destination * (1.0 - source.a());
+    let [r, g, b, a] = color
+        .to_array()
+        .map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Color32::from_rgba_premultiplied(r, g, b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a renderer with a white texture at the default texture ID.
+    fn white_renderer() -> SoftwareRenderer {
+        let mut renderer = SoftwareRenderer::new();
+        renderer.update_textures(&TexturesDelta {
+            set: vec![(
+                TextureId::default(),
+                ImageDelta::full(
+                    ColorImage::filled([1, 1], Color32::WHITE),
+                    TextureOptions::NEAREST,
+                ),
+            )],
+            free: Vec::new(),
+        });
+        renderer
+    }
+
+    /// Creates a primitive that fills the given rectangle with a color.
+    fn rect_primitive(rect: Rect, clip_rect: Rect, color: Color32) -> ClippedPrimitive {
+        let mut mesh = Mesh::default();
+        mesh.add_colored_rect(rect, color);
+        ClippedPrimitive {
+            clip_rect,
+            primitive: Primitive::Mesh(mesh),
+        }
+    }
+
+    #[test]
+    fn rectangles_are_filled() {
+        let rect = Rect::from_min_size(pos2(1.0, 1.0), vec2(2.0, 2.0));
+        let primitive = rect_primitive(
+            rect,
+            Rect::from_min_size(Pos2::ZERO, vec2(4.0, 4.0)),
+            Color32::RED,
+        );
+        let image = white_renderer().render(&[primitive], [4, 4], 1.0, Color32::BLACK);
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let inside = (1..3).contains(&x) && (1..3).contains(&y);
+                let expected = if inside { Color32::RED } else { Color32::BLACK };
+                assert_eq!(image[(x, y)], expected, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn primitives_are_clipped() {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(4.0, 4.0));
+        let clip_rect = Rect::from_min_size(Pos2::ZERO, vec2(1.0, 1.0));
+        let primitive = rect_primitive(rect, clip_rect, Color32::RED);
+        let image = white_renderer().render(&[primitive], [4, 4], 2.0, Color32::BLACK);
+
+        assert_eq!(image[(1, 1)], Color32::RED);
+        assert_eq!(image[(2, 2)], Color32::BLACK);
+    }
+
+    #[test]
+    fn missing_textures_are_skipped() {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(4.0, 4.0));
+        let primitive = rect_primitive(rect, rect, Color32::RED);
+        let image = SoftwareRenderer::new().render(&[primitive], [4, 4], 1.0, Color32::BLACK);
+        assert!(image.pixels.iter().all(|&x| x == Color32::BLACK));
+    }
+}
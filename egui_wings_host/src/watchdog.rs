@@ -0,0 +1,128 @@
+use egui_wings::*;
+use std::collections::*;
+use std::sync::*;
+use std::time::*;
+
+/// Interrupts plugins whose transactions run past their time budgets. A plugin stuck in a
+/// long or infinite loop never returns control to the host thread, so the deadlines are
+/// observed by a separate thread, which calls the host's interrupt handler once a deadline
+/// passes.
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    /// The state shared with the watchdog thread.
+    shared: Arc<WatchdogShared>,
+}
+
+impl Watchdog {
+    /// Sets the function that interrupts plugins, starting the watchdog thread if
+    /// it is not yet running.
+    pub fn set_handler(&self, handler: Arc<InterruptFn>) {
+        let mut state = self.shared.lock();
+        state.handler = Some(handler);
+        if !state.running {
+            state.running = true;
+            let shared = self.shared.clone();
+            std::thread::Builder::new()
+                .name("egui_wings watchdog".to_owned())
+                .spawn(move || shared.run())
+                .expect("Failed to spawn watchdog thread.");
+        }
+        self.shared.changed.notify_all();
+    }
+
+    /// Sets the time by which the plugin's transaction must finish.
+    pub fn set_deadline(&self, plugin: PluginId, deadline: Option<Instant>) {
+        let mut state = self.shared.lock();
+        match deadline {
+            Some(deadline) => state.deadlines.insert(plugin, deadline),
+            None => state.deadlines.remove(&plugin),
+        };
+        self.shared.changed.notify_all();
+    }
+
+    /// Removes the deadlines of all plugins, returning the plugins which were interrupted
+    /// since this was last called.
+    pub fn take_interrupted(&self) -> BTreeSet<PluginId> {
+        let mut state = self.shared.lock();
+        state.deadlines.clear();
+        std::mem::take(&mut state.interrupted)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Interrupts the plugin which is currently building its UI.
+pub(crate) type InterruptFn = dyn Fn(PluginId) + Send + Sync;
+
+/// The state shared between a [`Watchdog`] and its thread.
+#[derive(Default)]
+struct WatchdogShared {
+    /// Notifies the watchdog thread that its state changed.
+    changed: Condvar,
+    /// The watchdog state.
+    state: Mutex<WatchdogState>,
+}
+
+impl WatchdogShared {
+    /// Waits for deadlines to pass, interrupting the plugins which missed them, until the
+    /// watchdog is dropped.
+    fn run(&self) {
+        let mut state = self.lock();
+        while !state.stopped {
+            let now = Instant::now();
+            let earliest = state
+                .deadlines
+                .iter()
+                .min_by_key(|(_, deadline)| **deadline)
+                .map(|(plugin, deadline)| (*plugin, *deadline));
+
+            state = match earliest {
+                Some((plugin, deadline)) if deadline <= now => {
+                    state.deadlines.remove(&plugin);
+                    state.interrupted.insert(plugin);
+                    let handler = state.handler.clone();
+                    drop(state);
+                    if let Some(handler) = handler {
+                        handler(plugin);
+                    }
+                    self.lock()
+                }
+                Some((_, deadline)) => {
+                    self.changed
+                        .wait_timeout(state, deadline - now)
+                        .expect("Failed to lock watchdog state.")
+                        .0
+                }
+                None => self
+                    .changed
+                    .wait(state)
+                    .expect("Failed to lock watchdog state."),
+            };
+        }
+    }
+
+    /// Locks the watchdog state.
+    fn lock(&self) -> MutexGuard<'_, WatchdogState> {
+        self.state.lock().expect("Failed to lock watchdog state.")
+    }
+}
+
+/// The deadlines observed by the watchdog thread.
+#[derive(Default)]
+struct WatchdogState {
+    /// The time by which each plugin with a transaction in progress must finish.
+    deadlines: BTreeMap<PluginId, Instant>,
+    /// Interrupts the plugin which missed its deadline.
+    handler: Option<Arc<InterruptFn>>,
+    /// The plugins which were interrupted for missing their deadlines.
+    interrupted: BTreeSet<PluginId>,
+    /// Whether the watchdog thread has been started.
+    running: bool,
+    /// Whether the watchdog was dropped, in which case its thread should exit.
+    stopped: bool,
+}
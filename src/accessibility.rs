@@ -0,0 +1,65 @@
+use crate::*;
+use egui::accesskit::{Node, Role, Toggled};
+
+/// Records the information of each widget that was added during the current pass, so that
+/// the host can describe the widget. `egui` only records widget information in debug builds,
+/// so the information is recovered from the accessibility tree where it is missing.
+pub(crate) fn record_widget_infos(context: &Context) {
+    let exposed = private_hack::Context::from_context(context);
+    let mut ctx = exposed.0.write();
+
+    for viewport in ctx.viewports.values_mut() {
+        let pass = &mut viewport.this_pass;
+        let Some(state) = &pass.accesskit_state else {
+            continue;
+        };
+
+        for (id, node) in &state.nodes {
+            if pass.widgets.by_id.contains_key(id) {
+                pass.widgets
+                    .infos
+                    .entry(*id)
+                    .or_insert_with(|| widget_info(node));
+            }
+        }
+    }
+}
+
+/// Converts an accessibility node back into the widget information from which it was built.
+fn widget_info(node: &Node) -> WidgetInfo {
+    let typ = match node.role() {
+        Role::Label => WidgetType::Label,
+        Role::Link => WidgetType::Link,
+        Role::TextInput | Role::MultilineTextInput => WidgetType::TextEdit,
+        Role::Button => WidgetType::Button,
+        Role::Image => WidgetType::Image,
+        Role::CheckBox => WidgetType::Checkbox,
+        Role::RadioButton => WidgetType::RadioButton,
+        Role::RadioGroup => WidgetType::RadioGroup,
+        Role::ComboBox => WidgetType::ComboBox,
+        Role::Slider => WidgetType::Slider,
+        Role::SpinButton => WidgetType::DragValue,
+        Role::ColorWell => WidgetType::ColorButton,
+        Role::Pane => WidgetType::Panel,
+        Role::ProgressIndicator => WidgetType::ProgressIndicator,
+        Role::Window => WidgetType::Window,
+        _ => WidgetType::Other,
+    };
+
+    let mut info = WidgetInfo::new(typ);
+    info.enabled = !node.is_disabled();
+    if typ == WidgetType::Label {
+        info.label = node.value().map(str::to_owned);
+    } else {
+        info.label = node.label().map(str::to_owned);
+        info.current_text_value = node.value().map(str::to_owned);
+    }
+    info.value = node.numeric_value();
+    info.selected = match node.toggled() {
+        Some(Toggled::True) => Some(true),
+        Some(Toggled::False) => Some(false),
+        _ => None,
+    };
+    info.hint_text = node.placeholder().map(str::to_owned);
+    info
+}
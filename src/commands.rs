@@ -0,0 +1,126 @@
+use crate::*;
+use serde::de::*;
+
+/// A message that a plugin sends to the host from its UI, such as a request to export a
+/// document or open an asset. Commands are delivered to the host in the order that they
+/// were sent, after the transaction in which they were sent ends.
+pub trait EguiCommand: Serialize + DeserializeOwned + 'static {
+    /// Identifies the type of the command, so that the host can decode it.
+    const NAME: &'static str;
+}
+
+/// Queues a command to be delivered to the host at the end of the current transaction.
+/// The context must be one obtained from [`Egui::context`] or [`Egui::context_named`];
+/// this allows commands to be sent from UI code that only has access to `Ui::ctx`.
+pub fn send_command<T: EguiCommand>(context: &Context, command: &T) {
+    let command = PluginCommand::new(command);
+    context.data_mut(|x| {
+        x.get_temp_mut_or_default::<CommandQueue>(Id::NULL)
+            .0
+            .push(command)
+    });
+}
+
+/// Removes all commands which were queued in the context.
+pub(crate) fn take_commands(context: &Context) -> Vec<PluginCommand> {
+    context.data_mut(|x| {
+        x.remove_temp::<CommandQueue>(Id::NULL)
+            .unwrap_or_default()
+            .0
+    })
+}
+
+/// The commands which will be delivered to the host at the end of the current transaction.
+#[derive(Clone, Default)]
+struct CommandQueue(Vec<PluginCommand>);
+
+/// A command that a plugin sent to the host, which may be decoded into its original type.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginCommand {
+    /// The name of the command type.
+    name: String,
+    /// The command value, encoded as RON.
+    value: String,
+}
+
+impl PluginCommand {
+    /// Encodes the given command.
+    fn new<T: EguiCommand>(command: &T) -> Self {
+        Self {
+            name: T::NAME.to_owned(),
+            value: ron::to_string(command).expect("Failed to encode command."),
+        }
+    }
+
+    /// Gets the name of the command type.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether this command is of the given type.
+    pub fn is<T: EguiCommand>(&self) -> bool {
+        self.name == T::NAME
+    }
+
+    /// Decodes the command as the given type. Returns `None` if the command is of
+    /// another type, or if the plugin sent a malformed value.
+    pub fn decode<T: EguiCommand>(&self) -> Option<T> {
+        self.is::<T>()
+            .then(|| ron::from_str(&self.value).ok())
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A command which requests that an asset be opened.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct OpenAsset(String);
+
+    impl EguiCommand for OpenAsset {
+        const NAME: &'static str = "OpenAsset";
+    }
+
+    /// A command which requests that the document be exported.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Export {
+        /// The scale at which to export.
+        scale: u32,
+    }
+
+    impl EguiCommand for Export {
+        const NAME: &'static str = "Export";
+    }
+
+    #[test]
+    fn commands_are_taken_in_order() {
+        let context = Context::default();
+        send_command(&context, &OpenAsset("tree.png".to_owned()));
+        send_command(&context, &Export { scale: 2 });
+
+        let commands = take_commands(&context);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(
+            commands[0].decode::<OpenAsset>(),
+            Some(OpenAsset("tree.png".to_owned()))
+        );
+        assert_eq!(commands[1].decode::<Export>(), Some(Export { scale: 2 }));
+        assert!(take_commands(&context).is_empty());
+    }
+
+    #[test]
+    fn commands_only_decode_as_their_type() {
+        let command = PluginCommand::new(&Export { scale: 2 });
+        assert_eq!(command.name(), "Export");
+        assert!(command.is::<Export>() && !command.is::<OpenAsset>());
+        assert_eq!(command.decode::<OpenAsset>(), None);
+
+        let malformed = PluginCommand {
+            name: Export::NAME.to_owned(),
+            value: "(".to_owned(),
+        };
+        assert_eq!(malformed.decode::<Export>(), None);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::*;
+use serde::de::*;
+
+/// A value which may be dragged between the host and plugins, such as an asset from the
+/// host's asset browser. Values are encoded into a [`DragPayload`] so that they may cross
+/// the WASM boundary.
+pub trait EguiDragPayload: Serialize + DeserializeOwned + 'static {
+    /// Identifies the type of the payload, so that the recipient can decode it.
+    const NAME: &'static str;
+}
+
+/// A drag-and-drop payload which is shared between the host and plugins. Unlike other
+/// payloads, which exist only in the context where they were set, a `DragPayload` that is set
+/// with [`DragAndDrop::set_payload`] or [`Response::dnd_set_drag_payload`] is visible to the
+/// host and every plugin. It may be received with `Ui::dnd_drop_zone::<DragPayload, _>` or
+/// [`Response::dnd_release_payload`], and then decoded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DragPayload {
+    /// The name of the payload type.
+    name: String,
+    /// The encoded payload value.
+    bytes: Vec<u8>,
+}
+
+impl DragPayload {
+    /// Encodes the given value as RON.
+    pub fn new<T: EguiDragPayload>(value: &T) -> Self {
+        Self {
+            name: T::NAME.to_owned(),
+            bytes: ron::to_string(value)
+                .expect("Failed to encode drag payload.")
+                .into_bytes(),
+        }
+    }
+
+    /// Creates a payload from a type name and a value which was encoded by the caller.
+    pub fn from_bytes(name: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Gets the name of the payload type.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the encoded payload value.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Whether this payload is of the given type.
+    pub fn is<T: EguiDragPayload>(&self) -> bool {
+        self.name == T::NAME
+    }
+
+    /// Decodes the payload as the given type. Returns `None` if the payload is of
+    /// another type, or if the value is malformed.
+    pub fn decode<T: EguiDragPayload>(&self) -> Option<T> {
+        self.is::<T>()
+            .then(|| ron::de::from_bytes(&self.bytes).ok())
+            .flatten()
+    }
+}
+
+/// Gets the shared payload which is being dragged in the given context, if any.
+pub(crate) fn drag_payload(context: &Context) -> Option<Arc<DragPayload>> {
+    DragAndDrop::payload::<DragPayload>(context)
+}
+
+/// Replaces the shared payload which is being dragged in the given context. Payloads of
+/// other types are left unchanged if there is no shared payload.
+pub(crate) fn set_drag_payload(context: &Context, payload: Option<DragPayload>) {
+    match payload {
+        Some(payload) => DragAndDrop::set_payload(context, payload),
+        None => {
+            if DragAndDrop::has_payload_of_type::<DragPayload>(context) {
+                DragAndDrop::clear_payload(context);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An asset which may be dragged from the host into a plugin.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Asset(String);
+
+    impl EguiDragPayload for Asset {
+        const NAME: &'static str = "Asset";
+    }
+
+    /// Gets the asset which is being dragged in the context, if any.
+    fn dragged_asset(context: &Context) -> Option<Asset> {
+        drag_payload(context).and_then(|x| x.decode())
+    }
+
+    #[test]
+    fn payloads_are_shared_with_plugins() {
+        let host = Context::default();
+        host.begin_pass(RawInput::default());
+        DragAndDrop::set_payload(&host, DragPayload::new(&Asset("tree.png".to_owned())));
+
+        let plugin = Context::default();
+        CreateContextSnapshot::FromContext(host.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply(&plugin);
+        assert_eq!(dragged_asset(&plugin), Some(Asset("tree.png".to_owned())));
+
+        DragAndDrop::set_payload(&plugin, DragPayload::new(&Asset("rock.png".to_owned())));
+        CreateContextSnapshot::FromContext(plugin.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |_| false, None, false);
+        assert_eq!(dragged_asset(&host), Some(Asset("rock.png".to_owned())));
+
+        DragAndDrop::clear_payload(&plugin);
+        CreateContextSnapshot::FromContext(plugin, ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |_| false, None, false);
+        assert_eq!(dragged_asset(&host), None);
+        let _ = host.end_pass();
+    }
+
+    #[test]
+    fn other_payloads_are_kept() {
+        let host = Context::default();
+        host.begin_pass(RawInput::default());
+        DragAndDrop::set_payload(&host, 5u32);
+
+        let plugin = Context::default();
+        CreateContextSnapshot::FromContext(plugin, ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |_| false, None, false);
+        assert_eq!(DragAndDrop::payload::<u32>(&host).as_deref(), Some(&5));
+        let _ = host.end_pass();
+    }
+
+    #[test]
+    fn payloads_only_decode_as_their_type() {
+        let payload = DragPayload::from_bytes("Asset", "(");
+        assert!(payload.is::<Asset>());
+        assert_eq!(payload.decode::<Asset>(), None);
+        assert_eq!(
+            DragPayload::from_bytes("Other", "(\"a\")").decode::<Asset>(),
+            None
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use crate::*;
+
+/// Gets the files which were dropped onto the plugin's layers during the current pass.
+/// Plugins cannot access the host filesystem, so the files are identified by handles,
+/// whose contents may be read with [`Egui::read_dropped_file`]. The context must be one
+/// obtained from [`Egui::context`] or [`Egui::context_named`].
+pub fn dropped_files(context: &Context) -> Vec<PluginDroppedFile> {
+    context.data(|x| x.get_temp::<DroppedFiles>(Id::NULL).unwrap_or_default().0)
+}
+
+/// Stores the files that the host provided for the current transaction.
+pub(crate) fn set_dropped_files(context: &Context, files: Vec<PluginDroppedFile>) {
+    context.data_mut(|x| x.insert_temp(Id::NULL, DroppedFiles(files)));
+}
+
+/// The files which were dropped onto the plugin's layers during the current pass.
+#[derive(Clone, Default)]
+struct DroppedFiles(Vec<PluginDroppedFile>);
+
+/// Identifies a file that was dropped onto a plugin, so that its contents may be
+/// requested from the host.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FileHandle(u64);
+
+impl FileHandle {
+    /// Creates a new handle from the given raw value.
+    #[doc(hidden)]
+    pub fn from_raw(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Gets the raw value of this handle.
+    pub fn as_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A file that was dropped onto one of a plugin's layers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PluginDroppedFile {
+    /// The handle with which the file's contents may be read.
+    pub handle: FileHandle,
+    /// The viewport onto which the file was dropped.
+    pub viewport: ViewportId,
+    /// The name of the file, without its path.
+    pub name: String,
+    /// The MIME type of the file, if known.
+    pub mime: String,
+    /// When the file was last modified, if known.
+    pub last_modified: Option<std::time::SystemTime>,
+}
+
+impl PluginDroppedFile {
+    /// Describes the given file without revealing its path.
+    #[doc(hidden)]
+    pub fn new(handle: FileHandle, viewport: ViewportId, file: &DroppedFile) -> Self {
+        let file = input::sanitize_dropped_file(file);
+        Self {
+            handle,
+            viewport,
+            name: file.name,
+            mime: file.mime,
+            last_modified: file.last_modified,
+        }
+    }
+}
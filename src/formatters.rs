@@ -0,0 +1,48 @@
+use crate::*;
+use egui::style::NumberFormatter;
+use std::collections::*;
+
+/// The number formatters which have been registered in this module, keyed by name.
+static NUMBER_FORMATTERS: Mutex<BTreeMap<String, private_hack::NumberFormatter>> =
+    Mutex::new(BTreeMap::new());
+
+/// Registers a number formatter under the given name, replacing any formatter that was
+/// previously registered with the same name. A formatter cannot be sent across the WASM
+/// boundary, so when a style whose [`Style::number_formatter`](egui::Style::number_formatter)
+/// is a clone of a registered formatter is synchronized, only the name is sent. The recipient
+/// then uses the formatter which it registered under the same name.
+pub fn register_number_formatter(name: impl Into<String>, formatter: NumberFormatter) {
+    number_formatters().insert(name.into(), formatter.into());
+}
+
+/// Gets the number formatter which was registered under the given name, if any.
+pub fn number_formatter(name: &str) -> Option<NumberFormatter> {
+    number_formatters().get(name).cloned().map(Into::into)
+}
+
+/// Gets the name under which the given formatter was registered, if any.
+pub(crate) fn number_formatter_name(formatter: &private_hack::NumberFormatter) -> Option<String> {
+    number_formatters()
+        .iter()
+        .find(|(_, x)| Arc::ptr_eq(&x.0, &formatter.0))
+        .map(|(name, _)| name.clone())
+}
+
+/// Gets the formatter registered under the given name. If no formatter was registered,
+/// a formatter with the default behavior is registered in its place, so that the name
+/// is kept when the style is sent back.
+pub(crate) fn named_number_formatter(name: String) -> private_hack::NumberFormatter {
+    number_formatters()
+        .entry(name)
+        .or_insert_with(|| {
+            private_hack::NumberFormatter::new(egui::emath::format_with_decimals_in_range)
+        })
+        .clone()
+}
+
+/// Locks the number formatters.
+fn number_formatters() -> MutexGuard<'static, BTreeMap<String, private_hack::NumberFormatter>> {
+    NUMBER_FORMATTERS
+        .lock()
+        .expect("Failed to lock number formatters.")
+}
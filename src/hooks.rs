@@ -0,0 +1,84 @@
+use crate::*;
+
+/// The `egui::Plugin`s which `egui` registers on every context. The host runs its own
+/// copies of these, so they are not run on the guest.
+const BUILT_IN_PLUGINS: &[&str] = &["DebugTextPlugin", "DragAndDrop", "LabelSelectionState"];
+
+/// Invokes the given hook on every `egui::Plugin` registered on the context, in the order
+/// that they were added, except for those which `egui` registers itself.
+pub(crate) fn run(context: &Context, mut hook: impl FnMut(&mut dyn Plugin, &Context)) {
+    let plugins = {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+        ctx.plugins.plugins_ordered.0.clone()
+    };
+
+    for plugin in plugins {
+        let mut plugin = plugin.lock();
+        let plugin = &mut *plugin.plugin;
+        if !BUILT_IN_PLUGINS.contains(&plugin.debug_name()) {
+            hook(plugin, context);
+        }
+    }
+}
+
+/// Whether any `egui::Plugin` or pass callback has been registered on the context, other
+/// than those which `egui` registers itself.
+pub(crate) fn any(context: &Context) -> bool {
+    let plugins = {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+        ctx.plugins.plugins_ordered.0.clone()
+    };
+
+    plugins.iter().any(|plugin| {
+        let plugin = plugin.lock();
+        match private_hack::CallbackPlugin::from_plugin(&*plugin.plugin) {
+            Some(callbacks) => {
+                !callbacks.on_begin_plugins.is_empty() || !callbacks.on_end_plugins.is_empty()
+            }
+            None => !BUILT_IN_PLUGINS.contains(&plugin.plugin.debug_name()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plugin which counts the passes that it observed.
+    struct CountPasses(Arc<Mutex<u32>>);
+
+    impl Plugin for CountPasses {
+        fn debug_name(&self) -> &'static str {
+            "CountPasses"
+        }
+
+        fn on_end_pass(&mut self, _: &Context) {
+            *self.0.lock().expect("Failed to lock count.") += 1;
+        }
+    }
+
+    #[test]
+    fn built_in_plugins_are_not_hooks() {
+        assert!(!any(&Context::default()));
+    }
+
+    #[test]
+    fn pass_callbacks_are_hooks() {
+        let context = Context::default();
+        context.on_end_pass("callback", Arc::new(|_| {}));
+        assert!(any(&context));
+    }
+
+    #[test]
+    fn added_plugins_are_hooks() {
+        let context = Context::default();
+        let count = Arc::new(Mutex::new(0));
+        context.add_plugin(CountPasses(count.clone()));
+        assert!(any(&context));
+
+        run(&context, |plugin, ctx| plugin.on_end_pass(ctx));
+        assert_eq!(*count.lock().expect("Failed to lock count."), 1);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::*;
+use std::borrow::*;
+
+/// Describes the host input which should be withheld from a plugin during a transaction.
+#[doc(hidden)]
+#[derive(Clone, Debug, Default)]
+pub struct InputRedaction {
+    /// The input to withhold in each viewport. Viewports which are absent are not redacted.
+    pub viewports: ViewportIdMap<ViewportInputRedaction>,
+}
+
+impl InputRedaction {
+    /// Determines which input should be withheld from a plugin that owns the layers accepted
+    /// by `owns_layer`. A plugin receives keyboard input when one of its widgets has focus,
+    /// and pointer input when the pointer is over one of its layers or it is dragging a widget.
+    /// Keyboard events accepted by `is_shortcut` are delivered regardless of focus. Files are
+    /// delivered when they are dragged over one of the plugin's layers.
+    pub fn for_plugin(
+        context: &Context,
+        owns_layer: impl Fn(LayerId) -> bool,
+        mut is_shortcut: impl FnMut(&Event) -> bool,
+    ) -> Self {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        let mut viewports = ViewportIdMap::default();
+        for (id, viewport) in &ctx.viewports {
+            let widget_owned = |widget: Id| {
+                viewport
+                    .prev_pass
+                    .widgets
+                    .by_id
+                    .get(&widget)
+                    .is_some_and(|(_, rect)| owns_layer(rect.layer_id))
+            };
+
+            let focused = ctx
+                .memory
+                .focus
+                .get(id)
+                .and_then(|x| x.focused_widget.as_ref())
+                .is_some_and(|x| widget_owned(x.id));
+            let hovered = viewport
+                .hits
+                .contains_pointer
+                .last()
+                .is_some_and(|x| owns_layer(x.layer_id));
+            let dragging = viewport.interact_widgets.dragged.is_some_and(widget_owned);
+
+            let shortcuts = if focused {
+                Vec::new()
+            } else {
+                viewport
+                    .input
+                    .events
+                    .iter()
+                    .filter(|x| is_keyboard_event(x) && is_shortcut(x))
+                    .cloned()
+                    .collect()
+            };
+
+            viewports.insert(
+                *id,
+                ViewportInputRedaction {
+                    hide_pointer: !hovered && !dragging,
+                    hide_keyboard: !focused,
+                    receive_files: hovered,
+                    shortcuts,
+                },
+            );
+        }
+
+        Self { viewports }
+    }
+
+    /// Creates a redaction which withholds no input other than the files that are not dragged
+    /// over one of the layers accepted by `owns_layer`.
+    pub fn for_files(context: &Context, owns_layer: impl Fn(LayerId) -> bool) -> Self {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        let viewports = ctx
+            .viewports
+            .iter()
+            .map(|(id, viewport)| {
+                let hovered = viewport
+                    .hits
+                    .contains_pointer
+                    .last()
+                    .is_some_and(|x| owns_layer(x.layer_id));
+
+                (
+                    *id,
+                    ViewportInputRedaction {
+                        receive_files: hovered,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        Self { viewports }
+    }
+
+    /// Whether any files are being dragged over or were dropped onto the context.
+    pub fn has_files(context: &Context) -> bool {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+        ctx.viewports
+            .values()
+            .any(|x| !x.input.raw.dropped_files.is_empty() || !x.input.raw.hovered_files.is_empty())
+    }
+
+    /// Withholds all dragged and dropped files from the plugin.
+    pub fn deny_files(&mut self) {
+        for viewport in self.viewports.values_mut() {
+            viewport.receive_files = false;
+        }
+    }
+
+    /// Whether the plugin receives files in any viewport.
+    pub fn receives_files(&self) -> bool {
+        self.viewports.values().any(|x| x.receive_files)
+    }
+
+    /// Whether the plugin receives pointer and keyboard input in the same viewports as it would under the other redaction.
+    pub fn same_routing(&self, other: &Self) -> bool {
+        self.viewports.len() == other.viewports.len()
+            && self.viewports.iter().all(|(id, x)| {
+                other.viewports.get(id).is_some_and(|y| {
+                    x.hide_pointer == y.hide_pointer && x.hide_keyboard == y.hide_keyboard
+                })
+            })
+    }
+
+    /// Whether the plugin would observe any input events during the current pass.
+    pub fn has_events(&self, context: &Context) -> bool {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        ctx.viewports.iter().any(|(id, viewport)| {
+            let redaction = self.viewports.get(id);
+            let files = !viewport.input.raw.dropped_files.is_empty()
+                || !viewport.input.raw.hovered_files.is_empty();
+            viewport
+                .input
+                .events
+                .iter()
+                .any(|x| redaction.is_none_or(|redaction| redaction.keeps(x)))
+                || (files && redaction.is_none_or(|x| x.receive_files))
+        })
+    }
+
+    /// Gets the input state and widget hits that the plugin should observe for the given viewport.
+    pub(crate) fn redact<'a>(
+        &self,
+        id: ViewportId,
+        input: &'a InputState,
+        hits: &'a private_hack::WidgetHits,
+    ) -> (Cow<'a, InputState>, Cow<'a, private_hack::WidgetHits>) {
+        let files = !input.raw.dropped_files.is_empty() || !input.raw.hovered_files.is_empty();
+        let Some(redaction) = self
+            .viewports
+            .get(&id)
+            .filter(|x| x.hide_pointer || x.hide_keyboard || files)
+        else {
+            return (Cow::Borrowed(input), Cow::Borrowed(hits));
+        };
+
+        let mut input = input.clone();
+        input.events.retain(|x| redaction.keeps(x));
+        input.raw.events.retain(|x| redaction.keeps(x));
+
+        if redaction.receive_files {
+            for file in &mut input.raw.dropped_files {
+                *file = sanitize_dropped_file(file);
+            }
+            for file in &mut input.raw.hovered_files {
+                file.path = None;
+            }
+        } else {
+            input.raw.dropped_files.clear();
+            input.raw.hovered_files.clear();
+        }
+
+        if redaction.hide_keyboard {
+            input.keys_down.clear();
+        }
+
+        let hits = if redaction.hide_pointer {
+            input.pointer = Default::default();
+            input.raw_scroll_delta = Vec2::ZERO;
+            input.smooth_scroll_delta = Vec2::ZERO;
+            Cow::Owned(private_hack::WidgetHits::default())
+        } else {
+            Cow::Borrowed(hits)
+        };
+
+        (Cow::Owned(input), hits)
+    }
+}
+
+/// Describes the host input which should be withheld from a plugin in a single viewport.
+#[doc(hidden)]
+#[derive(Clone, Debug, Default)]
+pub struct ViewportInputRedaction {
+    /// Whether pointer, scroll and touch input is withheld.
+    pub hide_pointer: bool,
+    /// Whether keyboard, text and clipboard input is withheld.
+    pub hide_keyboard: bool,
+    /// Whether dragged and dropped files are delivered, without their paths or contents.
+    pub receive_files: bool,
+    /// Keyboard events which are delivered even though keyboard input is withheld.
+    pub shortcuts: Vec<Event>,
+}
+
+impl ViewportInputRedaction {
+    /// Whether the given event should be delivered to the plugin.
+    fn keeps(&self, event: &Event) -> bool {
+        if self.hide_pointer && is_pointer_event(event) {
+            false
+        } else if self.hide_keyboard && is_keyboard_event(event) {
+            self.shortcuts.contains(event)
+        } else {
+            true
+        }
+    }
+}
+
+/// A read-only view of the host's input, which a plugin obtains with [`Egui::input`]
+/// without a context transaction. Input that the host withholds from the plugin is removed,
+/// as it would be during a transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostInput {
+    /// The input state of the host's current viewport.
+    pub input: InputState,
+    /// Whether the pointer is over an `egui` area.
+    pub is_pointer_over_area: bool,
+    /// Whether `egui` is using the pointer, such as to drag a widget.
+    pub is_using_pointer: bool,
+    /// Whether `egui` is interested in keyboard input, such as when a text field has focus.
+    pub wants_keyboard_input: bool,
+    /// Whether `egui` is interested in pointer input, such as when the pointer is over an area.
+    pub wants_pointer_input: bool,
+}
+
+impl HostInput {
+    /// Captures the input of the given context, withholding any input described by the
+    /// redaction.
+    #[doc(hidden)]
+    pub fn capture(context: &Context, redaction: Option<&InputRedaction>) -> Self {
+        let input = context.input(|x| x.clone());
+        let input = match redaction {
+            Some(redaction) => redaction
+                .redact(
+                    context.viewport_id(),
+                    &input,
+                    &private_hack::WidgetHits::default(),
+                )
+                .0
+                .into_owned(),
+            None => input,
+        };
+
+        Self {
+            input,
+            is_pointer_over_area: context.is_pointer_over_area(),
+            is_using_pointer: context.is_using_pointer(),
+            wants_keyboard_input: context.wants_keyboard_input(),
+            wants_pointer_input: context.wants_pointer_input(),
+        }
+    }
+}
+
+/// Removes the path and contents of a dropped file, so that the file may only be read
+/// through the host. The file's name is preserved.
+pub(crate) fn sanitize_dropped_file(file: &DroppedFile) -> DroppedFile {
+    let name = if file.name.is_empty() {
+        file.path
+            .as_ref()
+            .and_then(|x| x.file_name())
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    } else {
+        file.name.clone()
+    };
+
+    DroppedFile {
+        path: None,
+        name,
+        mime: file.mime.clone(),
+        last_modified: file.last_modified,
+        bytes: None,
+    }
+}
+
+/// Whether the event originates from the keyboard, text input or clipboard.
+fn is_keyboard_event(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Copy
+            | Event::Cut
+            | Event::Paste(_)
+            | Event::Text(_)
+            | Event::Key { .. }
+            | Event::Ime(_)
+    )
+}
+
+/// Whether the event originates from a pointer or touch device.
+fn is_pointer_event(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::PointerMoved(_)
+            | Event::MouseMoved(_)
+            | Event::PointerButton { .. }
+            | Event::PointerGone
+            | Event::Zoom(_)
+            | Event::Rotate(_)
+            | Event::Touch { .. }
+            | Event::MouseWheel { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the keyboard and pointer state of a context.
+    fn input_state(context: &Context) -> (Vec<Event>, Vec<Key>, Option<Pos2>) {
+        context.input(|x| {
+            let mut keys = x.keys_down.iter().copied().collect::<Vec<_>>();
+            keys.sort();
+            (x.events.clone(), keys, x.pointer.latest_pos())
+        })
+    }
+
+    #[test]
+    fn host_input_survives_routed_plugin_transaction() {
+        let host = Context::default();
+        let _ = host.run(RawInput::default(), |_| {});
+        host.begin_pass(RawInput {
+            events: vec![
+                Event::Key {
+                    key: Key::A,
+                    physical_key: None,
+                    pressed: true,
+                    repeat: false,
+                    modifiers: Modifiers::NONE,
+                },
+                Event::Text("a".to_owned()),
+                Event::PointerMoved(pos2(10.0, 10.0)),
+            ],
+            ..Default::default()
+        });
+        let host_input = input_state(&host);
+
+        let plugin = Context::default();
+        let redaction = InputRedaction::for_plugin(&host, |_| false, |_| false);
+        CreateContextSnapshot::Redacted(host.clone(), ContextSnapshotDeltas::default(), redaction)
+            .transfer()
+            .apply(&plugin);
+        assert_eq!(input_state(&plugin), (Vec::new(), Vec::new(), None));
+
+        CreateContextSnapshot::FromContext(plugin.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |_| false, None, true);
+        assert_eq!(input_state(&host), host_input);
+        let _ = host.end_pass();
+    }
+
+    #[test]
+    fn host_dropped_files_survive_plugin_transaction() {
+        let host = Context::default();
+        host.begin_pass(RawInput {
+            dropped_files: vec![DroppedFile {
+                path: Some("/home/user/a.txt".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+
+        let plugin = Context::default();
+        let redaction = InputRedaction {
+            viewports: [(
+                ViewportId::ROOT,
+                ViewportInputRedaction {
+                    receive_files: true,
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        CreateContextSnapshot::Redacted(host.clone(), ContextSnapshotDeltas::default(), redaction)
+            .transfer()
+            .apply(&plugin);
+        let plugin_files = plugin.input(|x| x.raw.dropped_files.clone());
+        assert_eq!(plugin_files.len(), 1);
+        assert_eq!(plugin_files[0].path, None);
+        assert_eq!(plugin_files[0].name, "a.txt");
+
+        CreateContextSnapshot::FromContext(plugin.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |_| false, None, true);
+        let host_files = host.input(|x| x.raw.dropped_files.clone());
+        assert_eq!(host_files.len(), 1);
+        assert_eq!(host_files[0].path, Some("/home/user/a.txt".into()));
+        let _ = host.end_pass();
+    }
+}
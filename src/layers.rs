@@ -0,0 +1,198 @@
+use crate::*;
+use std::collections::hash_map::Entry;
+use std::collections::*;
+
+/// Every layer order, in the order that `GraphicLayers` stores them.
+pub(crate) const ORDERS: [Order; 5] = [
+    Order::Background,
+    Order::Middle,
+    Order::Foreground,
+    Order::Tooltip,
+    Order::Debug,
+];
+
+/// Gets every layer that is known to the context, across all viewports. This includes
+/// layers which have area state and layers which have been painted during the current pass.
+#[doc(hidden)]
+pub fn context_layer_ids(context: &Context) -> HashSet<LayerId> {
+    let exposed = private_hack::Context::from_context(context);
+    let ctx = exposed.0.read();
+
+    let mut result = HashSet::new();
+    for areas in ctx.memory.areas.values() {
+        result.extend(areas.order.iter().copied());
+    }
+
+    for viewport in ctx.viewports.values() {
+        for (order, paint_lists) in ORDERS.iter().zip(viewport.graphics.as_inner()) {
+            result.extend(paint_lists.keys().map(|id| LayerId::new(*order, *id)));
+        }
+    }
+
+    result
+}
+
+/// Gets every widget that was added during the most recently completed pass of each viewport,
+/// along with the widget's information if it was recorded. The rectangles of the widgets are
+/// transformed into screen space. Widgets are ordered from back to front.
+#[doc(hidden)]
+pub fn context_widgets(context: &Context) -> Vec<(ViewportId, WidgetRect, Option<WidgetInfo>)> {
+    let exposed = private_hack::Context::from_context(context);
+    let ctx = exposed.0.read();
+
+    let mut result = Vec::new();
+    for (viewport_id, viewport) in &ctx.viewports {
+        let widgets = &viewport.prev_pass.widgets;
+        let order = ctx
+            .memory
+            .areas
+            .get(viewport_id)
+            .map(|x| x.order.as_slice())
+            .unwrap_or_default();
+
+        let mut layers = widgets.by_layer.iter().collect::<Vec<_>>();
+        layers.sort_by_key(|(layer, _)| (layer.order, order.iter().position(|x| x == *layer)));
+        for (layer, rects) in layers {
+            let transform = ctx.memory.to_global.get(layer).copied().unwrap_or_default();
+            for rect in rects {
+                let widget = WidgetRect {
+                    id: rect.id,
+                    layer_id: rect.layer_id,
+                    rect: rect.rect,
+                    interact_rect: rect.interact_rect,
+                    sense: Sense::from_bits_retain(rect.sense.0),
+                    enabled: rect.enabled,
+                };
+                result.push((
+                    *viewport_id,
+                    widget.transform(transform),
+                    widgets.infos.get(&rect.id).cloned(),
+                ));
+            }
+        }
+    }
+
+    result
+}
+
+/// The shapes and widgets that were added to a set of layers, which may be reused during a later pass.
+#[doc(hidden)]
+#[derive(Clone, Default)]
+pub struct LayerGraphics {
+    /// The shapes painted to each layer, along with the viewport in which they were painted.
+    layers: Vec<(ViewportId, LayerId, Vec<epaint::ClippedShape>)>,
+    /// The widgets added to each layer, along with the viewport in which they were added.
+    widgets: Vec<(
+        ViewportId,
+        LayerId,
+        Vec<private_hack::widget_rect::WidgetRect>,
+    )>,
+    /// The widget information for each captured widget.
+    infos: Vec<(ViewportId, Id, WidgetInfo)>,
+    /// The rectangle and scale of each viewport when the layers were captured.
+    screens: Vec<(ViewportId, Rect, f32)>,
+}
+
+impl LayerGraphics {
+    /// Copies the shapes and widgets which have been added to the given layers during the current pass.
+    pub fn capture(context: &Context, layers: &HashSet<LayerId>) -> Self {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        let mut result = Self::default();
+        for (viewport_id, viewport) in &ctx.viewports {
+            for (order, paint_lists) in ORDERS.iter().zip(viewport.graphics.as_inner()) {
+                for (id, paint_list) in paint_lists {
+                    let layer = LayerId::new(*order, *id);
+                    if layers.contains(&layer) && !paint_list.as_inner().is_empty() {
+                        result
+                            .layers
+                            .push((*viewport_id, layer, paint_list.as_inner().clone()));
+                    }
+                }
+            }
+
+            let widgets = &viewport.this_pass.widgets;
+            for (layer, rects) in &widgets.by_layer {
+                if layers.contains(layer) {
+                    result.widgets.push((*viewport_id, *layer, rects.clone()));
+                    result.infos.extend(rects.iter().filter_map(|rect| {
+                        let info = widgets.infos.get(&rect.id)?;
+                        Some((*viewport_id, rect.id, info.clone()))
+                    }));
+                }
+            }
+
+            result.screens.push((
+                *viewport_id,
+                viewport.input.viewport_rect(),
+                viewport.input.pixels_per_point,
+            ));
+        }
+
+        result
+    }
+
+    /// Whether the viewports of the context have the same size and scale as when these
+    /// layers were captured, such that the captured layers would be laid out identically.
+    pub fn matches_screens(&self, context: &Context) -> bool {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        self.screens.iter().all(|(id, rect, pixels_per_point)| {
+            ctx.viewports.get(id).is_some_and(|x| {
+                x.input.viewport_rect() == *rect && x.input.pixels_per_point == *pixels_per_point
+            })
+        })
+    }
+
+    /// Adds the captured shapes and widgets to their layers during the current pass, and marks
+    /// the layers as visible so that they remain in the area order.
+    pub fn restore(&self, context: &Context) {
+        let exposed = private_hack::Context::from_context(context);
+        let mut ctx = exposed.0.write();
+        let ctx = &mut *ctx;
+
+        for (viewport_id, layer, shapes) in &self.layers {
+            let Some(viewport) = ctx.viewports.get_mut(viewport_id) else {
+                continue;
+            };
+
+            viewport.graphics.as_inner_mut()[layer.order as usize]
+                .entry(layer.id)
+                .or_default()
+                .as_inner_mut()
+                .extend(shapes.iter().cloned());
+
+            if let Some(areas) = ctx.memory.areas.get_mut(viewport_id) {
+                areas.visible_areas_current_frame.insert(*layer);
+            }
+        }
+
+        for (viewport_id, layer, rects) in &self.widgets {
+            let Some(viewport) = ctx.viewports.get_mut(viewport_id) else {
+                continue;
+            };
+
+            let widgets = &mut viewport.this_pass.widgets;
+            let layer_widgets = widgets.by_layer.entry(*layer).or_default();
+            for rect in rects {
+                if let Entry::Vacant(entry) = widgets.by_id.entry(rect.id) {
+                    entry.insert((layer_widgets.len(), rect.clone()));
+                    layer_widgets.push(rect.clone());
+                }
+            }
+        }
+
+        for (viewport_id, id, info) in &self.infos {
+            if let Some(viewport) = ctx.viewports.get_mut(viewport_id) {
+                viewport
+                    .this_pass
+                    .widgets
+                    .infos
+                    .entry(*id)
+                    .or_insert_with(|| info.clone());
+            }
+        }
+    }
+}
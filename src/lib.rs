@@ -49,9 +49,116 @@
 //!     }
 //! }
 //! ```
+//!
+//! ### AccessKit
+//!
+//! Enabling the `accesskit` feature allows widgets drawn by plugins to appear in the host's AccessKit tree. The feature changes the format of the data that is synchronized across the WASM boundary, so it must be enabled for the host (via `egui_wings_host/accesskit`) and for every guest.
+//!
+//! ### Persistence
+//!
+//! A plugin may call `Egui::set_persistence_key` with a stable name to have its `egui` state saved between sessions. The first plugin to set a key keeps it. The host saves and restores the state of all plugins with `EguiHost::save_plugin_states` and `EguiHost::load_plugin_states`. Window positions are always saved. The rest of a plugin's memory is saved if it enables the `persistence` feature, and reports it during the first transaction after `EguiHost::request_plugin_memory`.
+//!
+//! ### Images
+//!
+//! Images drawn by plugins, and any bytes or images that plugins load through `egui::load`, are loaded by the loaders installed on the host context. To prevent plugins from reading arbitrary files or making network requests, URIs other than the plugin's own image bytes are rejected unless the host permits their scheme with `EguiHost::set_allowed_uri_schemes`.
+//!
+//! ### Input routing
+//!
+//! By default, every plugin observes all of the host's input. With `InputPolicy::Routed`, set through `EguiHost::set_input_policy`, plugins receive keyboard input only while one of their widgets has focus, and pointer input only while the pointer is over one of their layers.
+//!
+//! ### Time budgets
+//!
+//! The host may limit the time that plugins spend building their UI with `EguiHost::set_time_budget`. The edits of a plugin that exceeds its budget are discarded, and its last frame within budget is shown instead. A plugin that is still running when its budget runs out is stopped by the handler given to `EguiHost::set_interrupt_handler`, which should make the WASM runtime trap, such as by incrementing the engine's epoch. After the trap, the host calls `EguiHost::recover_interrupted_plugins`.
+//!
+//! ### Retained frames
+//!
+//! Plugins whose UI rarely changes may call `Egui::context_if_changed` instead of `Egui::context`. If nothing relevant to the plugin changed, the host shows its previous frame again and no snapshot is exchanged.
+//!
+//! ### Deferred frames
+//!
+//! Between calls to `EguiHost::begin_deferred_frame` and `EguiHost::end_deferred_frame`, every plugin builds its UI from the host state at the beginning of the frame, and its edits are merged when the frame ends. Plugins still run one after another, but their edits are merged in order of plugin identifier, so the result does not depend on which plugin ran first.
+//!
+//! ### Window order
+//!
+//! The host keeps its own window order when a plugin's edits are applied. A plugin may only move or raise the windows that it owns, and requests to bring windows to the front are resolved together when the host's pass ends.
+//!
+//! ### Multiple contexts
+//!
+//! A host may share more than one `egui::Context` with plugins by registering each under a name with `EguiHost::register_context`. Plugins select them with `Egui::context_named`, and each context is synchronized independently.
+//!
+//! ### Offscreen surfaces
+//!
+//! For in-game monitors and other diegetic UI, the host may register the context of an `OffscreenSurface` and render the `OffscreenFrame` returned by `OffscreenSurface::run` into a texture. Pointer input is given in texture coordinates, so the UV of a ray hit may be passed directly to the plugin's UI.
+//!
+//! ### Commands
+//!
+//! Plugins may send any serializable type that implements `EguiCommand` to the host with `EguiHandle::send_command`. The host receives them in order with `EguiHost::take_commands` and decodes each one with `PluginCommand::decode`.
+//!
+//! ### Host widgets
+//!
+//! Widgets that only the host can provide, such as a native file picker, are registered with `EguiHost::register_widget`. Plugins place them with `HostWidget`, and the host draws them into the reserved space, delivering their responses during the plugin's next transaction.
+//!
+//! ### Dropped files
+//!
+//! Plugins only see the names and types of files dropped over their layers. `egui_wings::dropped_files` identifies each file by an opaque handle, which the plugin may exchange for the file's contents with `Egui::read_dropped_file`, unless the host forbids it with `EguiHost::set_file_drop_policy`.
+//!
+//! ### Pass hooks
+//!
+//! `egui::Plugin`s and pass callbacks registered on a plugin's context run when the host wraps the UI of a context in `EguiHost::run_pass_hooks`. Plugins handle the resulting `egui_wings::on::BeginPass` and `egui_wings::on::EndPass` events by calling `Egui::run_begin_pass_hooks` and `Egui::run_end_pass_hooks`.
+//!
+//! ### Testing
+//!
+//! The `kittest` feature of `egui_wings_host` allows plugin UIs to be tested without a window. `egui_wings_host::wings_harness` loads plugins into a headless `WingsHost` and returns an `egui_kittest` harness, in which plugin widgets may be queried and clicked like native ones if the plugin enables the `accesskit` feature.
+//!
+//! ### Golden images
+//!
+//! The `golden` feature of `egui_wings_host` allows plugin frames to be checked in CI without a GPU. `SoftwareRenderer` rasterizes the host's output on the CPU, and `compare_golden` compares it against a stored PNG, writing the rendered image and a diff when they differ.
+//!
+//! ### Paint-only overlays
+//!
+//! Plugins which only draw overlays may call `Egui::paint` instead of `Egui::context`. It sends the shapes of a single layer to the host without a transaction, and text is laid out by the host.
+//!
+//! ### Input queries
+//!
+//! Plugins which only need to read input may call `Egui::input` instead of `Egui::context`. It returns the host's current `InputState` without a transaction, with any input that is withheld from the plugin removed.
+//!
+//! ### Raw input sync
+//!
+//! With `EguiHost::set_input_sync_mode(InputSyncMode::Raw)`, the host sends the `RawInput` of each pass once instead of its processed `InputState`, and the plugin rebuilds the input itself. Processed input is still sent when the plugin missed the previous pass or when input is withheld from it.
+//!
+//! ### Graphics policies
+//!
+//! By default, plugins receive every shape painted during the current pass. With `GraphicsPolicy::Owned`, set through `EguiHost::set_graphics_policy`, plugins only receive the shapes and widget information of their own layers, which keeps the contents of host widgets away from them.
+//!
+//! ### Selection and clipboard
+//!
+//! Text selected in a label belongs to the plugin which owns the label's layer, and is hidden from other plugins. Text and images copied to the clipboard are delivered only to the host.
+//!
+//! ### Debug options and number formatters
+//!
+//! In debug builds, a style's `DebugOptions` are synchronized along with it. Number formatters cannot cross the WASM boundary, so they may be registered under a name with `register_number_formatter`, and only the name is sent.
+//!
+//! ### Widget automation
+//!
+//! The host may list the widgets that a plugin added to its own layers with `EguiHost::plugin_widgets`. Automation scripts may then operate them with `EguiHost::click_widget`, `EguiHost::type_into_widget` and `EguiHost::drag_widget`, which synthesize input over the following passes.
+//!
+//! ### Drag and drop
+//!
+//! Payloads set with `egui::DragAndDrop` usually exist only in the context where they were set. A `DragPayload`, created from any type which implements `EguiDragPayload`, is shared with the host and every plugin instead, and may be decoded with `DragPayload::decode` when it is dropped.
 
+pub use crate::commands::*;
+pub use crate::drag::*;
+pub use crate::files::*;
+pub use crate::formatters::*;
+pub use crate::input::*;
+pub use crate::layers::*;
+pub use crate::loaders::*;
+pub use crate::merge::*;
+pub use crate::paint::*;
+pub use crate::persistence::*;
 pub use crate::snapshot::ContextSnapshotDeltas;
 use crate::snapshot::*;
+pub use crate::widgets::*;
 /// Exports the `egui` crate for easy use.
 pub use egui;
 use egui::*;
@@ -60,6 +167,46 @@ use std::ops::*;
 use std::sync::*;
 use wings::*;
 
+/// Recovers the information of plugin widgets from the accessibility tree.
+#[cfg(feature = "accesskit")]
+mod accessibility;
+
+/// Allows plugins to send typed messages to the host.
+mod commands;
+
+/// Allows drag-and-drop payloads to be shared between the host and plugins.
+mod drag;
+
+/// Mediates access to files which are dropped onto plugins.
+mod files;
+
+/// Allows number formatters to be referred to by name across the WASM boundary.
+mod formatters;
+
+/// Runs the pass hooks of `egui::Plugin`s registered on the guest.
+mod hooks;
+
+/// Determines which host input is visible to plugins.
+mod input;
+
+/// Provides utilities for inspecting the layers of a context.
+mod layers;
+
+/// Forwards image loading requests to the host.
+mod loaders;
+
+/// Combines the output of plugins which built their UI from the same state.
+mod merge;
+
+/// Allows plugins to paint shapes without a context transaction.
+mod paint;
+
+/// Stores plugin state between application sessions.
+mod persistence;
+
+/// Tracks repaint requests made by plugins.
+mod repaint;
+
 /// Abuses compiler behavior to get access to `egui`'s private state, so that
 /// it may be serialized. Temporary hack until [`https://github.com/emilk/egui/pull/4930`]
 mod private_hack;
@@ -67,46 +214,339 @@ mod private_hack;
 /// Holds the serialization logic for taking snapshots.
 mod snapshot;
 
-/// The inner context which temporarily stores state that will be copied to the host.
-static CONTEXT: OnceLock<Context> = OnceLock::new();
+/// Allows plugins to place widgets which are implemented by the host.
+mod widgets;
+
+/// The events which the host raises for plugins.
+pub mod on {
+    use super::*;
+
+    /// Raised by the host at the beginning of each pass of a context, after the
+    /// pass has begun but before any other UI is built.
+    #[export_type]
+    pub struct BeginPass {
+        /// The name of the context. The empty name refers to the default context.
+        pub context: String,
+    }
+
+    /// Raised by the host at the end of each pass of a context, after all
+    /// other UI has been built but before the pass ends.
+    #[export_type]
+    pub struct EndPass {
+        /// The name of the context. The empty name refers to the default context.
+        pub context: String,
+    }
+}
+
+/// The name of the host's default context.
+const DEFAULT_CONTEXT: &str = "";
+
+/// The inner contexts which temporarily store state that will be copied to the host,
+/// keyed by the name of the corresponding host context.
+static CONTEXTS: Mutex<std::collections::BTreeMap<String, Context>> =
+    Mutex::new(std::collections::BTreeMap::new());
+
+/// The identifier that the host assigned to this plugin.
+static PLUGIN_ID: OnceLock<PluginId> = OnceLock::new();
+
+/// Persisted memory which was restored by the host before the inner context was created.
+#[cfg(feature = "persistence")]
+static RESTORED_MEMORY: Mutex<Option<String>> = Mutex::new(None);
+
+/// Uniquely identifies a guest module which shares the host `egui::Context`.
+/// All systems within the same WASM module share a single identifier.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PluginId(u64);
+
+impl PluginId {
+    /// Creates a new identifier from the given raw value.
+    #[doc(hidden)]
+    pub fn from_raw(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Gets the raw value of this identifier.
+    pub fn as_raw(&self) -> u64 {
+        self.0
+    }
+}
 
 /// Allows for accessing the shared `egui::Context` for plugins.
 #[system_trait(host)]
 pub trait Egui: 'static {
-    /// Begins a context transaction by obtaining a snapshot containing the state
-    /// of the host's `egui::Context` so that it may be edited on the guest.
+    /// Assigns a new, unique identifier to the calling plugin.
+    #[doc(hidden)]
+    fn register_plugin(&self) -> PluginId;
+
+    /// Associates the plugin with a stable persistence key, restoring any host state
+    /// saved under that key. Returns the plugin's persisted memory, if any. Keys which
+    /// belong to another plugin are ignored.
+    #[doc(hidden)]
+    fn restore_plugin(&self, plugin: PluginId, key: String) -> Option<String>;
+
+    /// Provides the host with the plugin's persisted memory, encoded as RON.
+    #[doc(hidden)]
+    fn save_plugin_memory(&self, plugin: PluginId, memory: String);
+
+    /// Loads the requested bytes using the loaders installed on the host context.
+    /// Returns one response for each request, in the same order.
+    #[doc(hidden)]
+    fn load_bytes(
+        &self,
+        plugin: PluginId,
+        context: String,
+        requests: Vec<BytesRequest>,
+    ) -> Vec<BytesResponse>;
+
+    /// Decodes the requested images using the loaders installed on the host context.
+    /// Returns one response for each request, in the same order.
+    #[doc(hidden)]
+    fn load_images(
+        &self,
+        plugin: PluginId,
+        context: String,
+        requests: Vec<ImageRequest>,
+    ) -> Vec<ImageResponse>;
+
+    /// Loads the requested textures using the loaders installed on the host context.
+    /// Returns one response for each request, in the same order.
+    #[doc(hidden)]
+    fn load_textures(
+        &self,
+        plugin: PluginId,
+        context: String,
+        requests: Vec<TextureRequest>,
+    ) -> Vec<TextureResponse>;
+
+    /// Reads the contents of a file that was dropped onto the plugin. Returns `None` if the
+    /// handle is unknown to the plugin or the file could not be read.
+    #[doc(hidden)]
+    fn load_dropped_file(&self, plugin: PluginId, file: FileHandle) -> Option<Vec<u8>>;
+
+    /// Begins a context transaction by obtaining a snapshot containing the state of the named
+    /// host `egui::Context` so that it may be edited on the guest. Returns `None` if the host
+    /// has no context with the given name.
+    #[doc(hidden)]
+    fn begin_context_edit(
+        &self,
+        plugin: PluginId,
+        context: String,
+        deltas: ContextSnapshotDeltas,
+    ) -> Option<ContextEditStart>;
+
+    /// Updates the named host `egui::Context` to use the given guest state, finishing the transaction.
     #[doc(hidden)]
-    fn begin_context_edit(&self, deltas: ContextSnapshotDeltas) -> CreateContextSnapshot;
+    fn end_context_edit(&self, plugin: PluginId, context: String, end: ContextEditEnd);
 
-    /// Updates the host `egui::Context` to use the given guest state, finishing the transaction.
+    /// Shows the plugin's previously committed frame in the named context again if nothing
+    /// relevant to the plugin has changed, so that the plugin need not build its UI. Returns
+    /// whether the frame was reused.
     #[doc(hidden)]
-    fn end_context_edit(&self, state: CreateContextSnapshot);
+    fn reuse_frame(&self, plugin: PluginId, context: String) -> bool;
+
+    /// Gets the input of the named host `egui::Context` that the plugin may observe, without
+    /// a transaction. Returns `None` if the host has no context with the given name.
+    #[doc(hidden)]
+    fn query_input(&self, plugin: PluginId, context: String) -> Option<HostInput>;
+
+    /// Paints shapes onto a layer of the named host `egui::Context` without a transaction.
+    #[doc(hidden)]
+    fn paint_layer(&self, plugin: PluginId, context: String, paint: LayerPaint);
 }
 
 impl dyn Egui {
     /// Initiates an `egui` transaction and produces a temporary handle to the `egui::Context`.
     pub fn context(&'_ self) -> EguiHandle<'_> {
-        let mut initialized = false;
-        let context = CONTEXT.get_or_init(|| {
-            let result = Context::default();
-            result.begin_pass(RawInput::default());
-            initialized = true;
-            result
-        });
+        self.context_named(DEFAULT_CONTEXT)
+            .expect("Failed to get default egui context.")
+    }
 
-        let deltas = if initialized {
-            ContextSnapshotDeltas::default()
-        } else {
-            ContextSnapshotDeltas::from_context(context)
+    /// Initiates an `egui` transaction with the host context registered under the given name,
+    /// and produces a temporary handle to it. Each named context has its own state and is
+    /// synchronized independently. The empty name refers to the host's default context.
+    /// Returns `None` if the host has no context with the given name.
+    pub fn context_named(&'_ self, name: &str) -> Option<EguiHandle<'_>> {
+        let plugin = self.plugin_id();
+        let existing = contexts().get(name).cloned();
+        let (context, deltas) = match existing {
+            Some(context) => {
+                let deltas = ContextSnapshotDeltas::from_context(&context);
+                (context, deltas)
+            }
+            None => (Self::create_context(name), ContextSnapshotDeltas::default()),
         };
 
-        self.begin_context_edit(deltas).apply(context);
-        let initial_deltas = ContextSnapshotDeltas::from_context(context);
+        let start = self.begin_context_edit(plugin, name.to_owned(), deltas)?;
+        contexts()
+            .entry(name.to_owned())
+            .or_insert_with(|| context.clone());
+        start
+            .snapshot
+            .decode()
+            .expect("Failed to decode host context snapshot.")
+            .apply(&context);
+        repaint::reset(&context);
+        widgets::add_responses(&context, start.widget_responses);
+        files::set_dropped_files(&context, start.dropped_files);
+        let initial_deltas = ContextSnapshotDeltas::from_context(&context);
+        let replayed_input = context.data(|x| {
+            x.get_temp::<AppliedInput>(Id::NULL)
+                .is_some_and(|x| x.replayed)
+        });
 
-        EguiHandle {
+        Some(EguiHandle {
             ctx: self,
+            context,
             initial_deltas,
+            name: name.to_owned(),
+            plugin,
+            replayed_input,
+            save_memory: start.save_memory,
+        })
+    }
+
+    /// Initiates an `egui` transaction like [`Self::context`], unless nothing relevant to this
+    /// plugin has changed since its last frame. In that case, the host shows the plugin's last
+    /// frame again and `None` is returned, so the plugin may skip building its UI. A plugin's
+    /// frame is considered unchanged when it received no input, requested no repaint, and the
+    /// host's screen size and style are the same.
+    pub fn context_if_changed(&'_ self) -> Option<EguiHandle<'_>> {
+        self.context_named_if_changed(DEFAULT_CONTEXT)
+    }
+
+    /// Initiates an `egui` transaction like [`Self::context_named`], unless nothing relevant to
+    /// this plugin has changed since its last frame in the named context, as described by
+    /// [`Self::context_if_changed`].
+    pub fn context_named_if_changed(&'_ self, name: &str) -> Option<EguiHandle<'_>> {
+        let reused = self.reuse_frame(self.plugin_id(), name.to_owned());
+        (!reused).then(|| self.context_named(name)).flatten()
+    }
+
+    /// Reads the input of the host's default context, without the cost of a transaction.
+    /// This suits plugins which only need the pointer position, modifiers, pressed keys or
+    /// whether `egui` wants input, since no context state is sent to or from the plugin.
+    /// Input that the host withholds from the plugin is removed.
+    pub fn input(&self) -> HostInput {
+        self.input_named(DEFAULT_CONTEXT)
+            .expect("Failed to get default egui context.")
+    }
+
+    /// Reads the input of the host context registered under the given name, as described
+    /// by [`Self::input`]. Returns `None` if the host has no context with the given name.
+    pub fn input_named(&self, name: &str) -> Option<HostInput> {
+        self.query_input(self.plugin_id(), name.to_owned())
+    }
+
+    /// Paints shapes onto a layer of the host's default context, without the cost of a
+    /// transaction. This suits plugins which only draw overlays and never need widgets or
+    /// input, since no context state is sent to or from the plugin. It should be called
+    /// while the host is building its UI, such as when handling the event that prompts
+    /// plugins to draw. Layers which belong to other plugins cannot be painted.
+    pub fn paint(&self, layer: LayerId, shapes: impl IntoIterator<Item = impl Into<PaintShape>>) {
+        self.paint_named(DEFAULT_CONTEXT, layer, shapes);
+    }
+
+    /// Paints shapes onto a layer of the host context registered under the given name, as
+    /// described by [`Self::paint`]. Nothing is painted if the host has no such context.
+    pub fn paint_named(
+        &self,
+        name: &str,
+        layer: LayerId,
+        shapes: impl IntoIterator<Item = impl Into<PaintShape>>,
+    ) {
+        let shapes = shapes.into_iter().map(Into::into).collect();
+        self.paint_layer(
+            self.plugin_id(),
+            name.to_owned(),
+            LayerPaint::new(layer, shapes),
+        );
+    }
+
+    /// Associates this plugin with a stable key, under which the host persists the plugin's
+    /// `egui` state between application sessions. Any state previously saved under the key is
+    /// restored. If another plugin already uses the key, nothing is restored or saved. This
+    /// should be called before the first call to [`Self::context`].
+    pub fn set_persistence_key(&self, key: impl Into<String>) {
+        let memory = self.restore_plugin(self.plugin_id(), key.into());
+
+        #[cfg(feature = "persistence")]
+        if let Some(memory) = memory {
+            if let Some(context) = contexts().get(DEFAULT_CONTEXT) {
+                persistence::load_memory(context, &memory);
+            } else {
+                *RESTORED_MEMORY
+                    .lock()
+                    .expect("Failed to lock restored memory.") = Some(memory);
+            }
         }
+
+        #[cfg(not(feature = "persistence"))]
+        let _ = memory;
+    }
+
+    /// Runs the [`Plugin::on_begin_pass`] hooks of the `egui::Plugin`s registered on this
+    /// plugin's context, including callbacks added with [`Context::on_begin_pass`]. This
+    /// should be called when handling [`on::BeginPass`], so that the hooks run at the
+    /// beginning of the host's pass. No transaction is made if no hooks are registered.
+    pub fn run_begin_pass_hooks(&self, event: &on::BeginPass) {
+        if !Self::has_pass_hooks(&event.context) {
+            return;
+        }
+
+        if let Some(ctx) = self.context_named(&event.context) {
+            hooks::run(&ctx, |plugin, ctx| plugin.on_begin_pass(ctx));
+        }
+    }
+
+    /// Runs the [`Plugin::on_end_pass`] hooks of the `egui::Plugin`s registered on this
+    /// plugin's context, including callbacks added with [`Context::on_end_pass`]. This
+    /// should be called when handling [`on::EndPass`], so that the hooks run after all
+    /// other UI in the host's pass, such as to draw overlays. No transaction is made if no
+    /// hooks are registered.
+    pub fn run_end_pass_hooks(&self, event: &on::EndPass) {
+        if !Self::has_pass_hooks(&event.context) {
+            return;
+        }
+
+        if let Some(ctx) = self.context_named(&event.context) {
+            hooks::run(&ctx, |plugin, ctx| plugin.on_end_pass(ctx));
+        }
+    }
+
+    /// Whether any pass hooks are registered on this plugin's copy of the named context.
+    fn has_pass_hooks(name: &str) -> bool {
+        let context = contexts().get(name).cloned();
+        context.is_some_and(|x| hooks::any(&x))
+    }
+
+    /// Reads the contents of a file that was dropped onto this plugin, as reported by
+    /// [`dropped_files`]. Returns `None` if the file could not be read, or if the host
+    /// has since forgotten the file because other files were dropped onto the plugin.
+    pub fn read_dropped_file(&self, file: FileHandle) -> Option<Vec<u8>> {
+        self.load_dropped_file(self.plugin_id(), file)
+    }
+
+    /// Gets the identifier that the host assigned to this plugin.
+    pub fn plugin_id(&self) -> PluginId {
+        *PLUGIN_ID.get_or_init(|| self.register_plugin())
+    }
+
+    /// Creates the inner context which will mirror the named host context.
+    fn create_context(name: &str) -> Context {
+        let result = Context::default();
+        result.begin_pass(RawInput::default());
+        loaders::install(&result, name);
+        #[cfg(feature = "persistence")]
+        if name == DEFAULT_CONTEXT {
+            if let Some(memory) = RESTORED_MEMORY
+                .lock()
+                .expect("Failed to lock restored memory.")
+                .take()
+            {
+                persistence::load_memory(&result, &memory);
+            }
+        }
+        result
     }
 }
 
@@ -116,45 +556,198 @@ impl dyn Egui {
 pub struct EguiHandle<'a> {
     /// The underlying `egui` context.
     ctx: &'a dyn Egui,
+    /// The inner context which mirrors the host context.
+    context: Context,
     /// The state of the context at the beginning of the transaction.
     initial_deltas: ContextSnapshotDeltas,
+    /// The name of the host context.
+    name: String,
+    /// The identifier of this plugin.
+    plugin: PluginId,
+    /// Whether the plugin rebuilt the host's input from raw input, in which case the input
+    /// is not sent back to the host.
+    replayed_input: bool,
+    /// Whether the host requested the plugin's persisted memory.
+    #[cfg_attr(not(feature = "persistence"), allow(dead_code))]
+    save_memory: bool,
+}
+
+impl<'a> EguiHandle<'a> {
+    /// Queues a command to be delivered to the host when this transaction ends.
+    /// This is equivalent to calling [`send_command`] with the handle's context.
+    pub fn send_command<T: EguiCommand>(&self, command: &T) {
+        send_command(self, command);
+    }
 }
 
 impl<'a> Deref for EguiHandle<'a> {
     type Target = Context;
 
     fn deref(&self) -> &Self::Target {
-        CONTEXT.get().expect("Failed to get egui context.")
+        &self.context
     }
 }
 
 impl<'a> Drop for EguiHandle<'a> {
     fn drop(&mut self) {
-        self.ctx
-            .end_context_edit(CreateContextSnapshot::FromContext(
-                self.clone(),
-                self.initial_deltas,
-            ));
+        #[cfg(feature = "persistence")]
+        if self.save_memory {
+            if let Some(memory) = persistence::save_memory(self) {
+                self.ctx.save_plugin_memory(self.plugin, memory);
+            }
+        }
+
+        loaders::flush(self.ctx, self.plugin, &self.name);
+        #[cfg(feature = "accesskit")]
+        accessibility::record_widget_infos(self);
+        self.ctx.end_context_edit(
+            self.plugin,
+            self.name.clone(),
+            ContextEditEnd {
+                commands: commands::take_commands(self),
+                snapshot: EncodedSnapshot::encode(&if self.replayed_input {
+                    CreateContextSnapshot::Filtered(
+                        self.clone(),
+                        self.initial_deltas,
+                        SnapshotFilter {
+                            replay_input: true,
+                            ..Default::default()
+                        },
+                    )
+                } else {
+                    CreateContextSnapshot::FromContext(self.clone(), self.initial_deltas)
+                }),
+                repaint: repaint::requests(self),
+                widgets: widgets::take_placements(self),
+            },
+        );
     }
 }
 
+/// The data that the host provides at the beginning of a context transaction.
+#[doc(hidden)]
+#[derive(Serialize, Deserialize)]
+pub struct ContextEditStart {
+    /// The files which were dropped onto the plugin's layers during the current pass.
+    pub dropped_files: Vec<PluginDroppedFile>,
+    /// The state of the host context.
+    pub snapshot: EncodedSnapshot,
+    /// Whether the plugin should report its persisted memory during this transaction.
+    pub save_memory: bool,
+    /// The responses of the host widgets that the plugin placed, which it has not yet received.
+    pub widget_responses: Vec<(Id, HostWidgetResponse)>,
+}
+
+/// The data that the plugin provides at the end of a context transaction.
+#[doc(hidden)]
+#[derive(Serialize, Deserialize)]
+pub struct ContextEditEnd {
+    /// The commands that the plugin sent during the transaction, in order.
+    pub commands: Vec<PluginCommand>,
+    /// The state of the guest context.
+    pub snapshot: EncodedSnapshot,
+    /// The repaint delay that the plugin requested for each viewport.
+    pub repaint: ViewportIdMap<std::time::Duration>,
+    /// The host widgets that the plugin placed during the transaction.
+    pub widgets: Vec<HostWidgetPlacement>,
+}
+
+/// Locks the inner contexts.
+fn contexts() -> MutexGuard<'static, std::collections::BTreeMap<String, Context>> {
+    CONTEXTS.lock().expect("Failed to lock egui contexts.")
+}
+
 /// Allows for serializing a `ContextSnapshot` across the WASM boundary.
 #[doc(hidden)]
 pub enum CreateContextSnapshot {
     /// This variant is used whenever a snapshot is deserialized.
-    Created(ContextSnapshot),
+    Created(Box<ContextSnapshot>),
     /// When this object is serialized, it will use a snapshot of the provided
     /// context with the given deltas.
     FromContext(Context, ContextSnapshotDeltas),
+    /// When this object is serialized, it will use a snapshot of the provided
+    /// context with the given deltas, withholding the redacted input.
+    Redacted(Context, ContextSnapshotDeltas, InputRedaction),
+    /// When this object is serialized, it will use a snapshot of the provided context with
+    /// the given deltas, withholding the data described by the filter.
+    Filtered(Context, ContextSnapshotDeltas, SnapshotFilter),
+}
+
+/// Describes which parts of a context are withheld when it is serialized.
+#[doc(hidden)]
+#[derive(Clone, Default)]
+pub struct SnapshotFilter {
+    /// The layers whose shapes, widgets and AccessKit nodes are sent, or `None` if those of
+    /// every layer are sent. Output events do not record the layer of their widget, so they
+    /// are only sent if every layer is.
+    pub layers: Option<std::collections::HashSet<LayerId>>,
+    /// The layers which the recipient owns, if it is a plugin. Text selected in other layers
+    /// and text or images copied to the clipboard are withheld from plugins.
+    pub owned_layers: Option<std::collections::HashSet<LayerId>>,
+    /// The input which should be withheld, if any.
+    pub redaction: Option<InputRedaction>,
+    /// Whether the recipient should rebuild its input from raw input where possible. Where
+    /// the recipient holds the input of the previous pass, only the raw input of the current
+    /// pass is sent.
+    pub replay_input: bool,
 }
 
 impl CreateContextSnapshot {
     /// Applies the snapshot to the current context. Panics if this snapshot is
     /// not the `Created` variant.
     pub fn apply(self, context: &Context) {
+        self.apply_with_areas(context, None, None, false);
+    }
+
+    /// Applies a snapshot produced by a plugin to the host context. Unlike [`Self::apply`],
+    /// the host's area state is merged with the plugin's rather than replaced, so that the
+    /// plugin cannot reorder layers which it does not own. The host's text selection and
+    /// clipboard contents, which plugins do not receive, are kept. If the plugin only received
+    /// the shapes of `visible_layers`, the shapes that it painted onto other layers are added
+    /// to the host's, the host's widgets for other layers are kept, and the plugin's output
+    /// events are added to the host's, which it did not receive. If `keep_input` is set,
+    /// because the plugin received redacted input, the host's input and widget hits are kept.
+    /// Panics if this snapshot is not the `Created` variant.
+    pub fn apply_from_plugin(
+        self,
+        context: &Context,
+        owns_layer: impl Fn(LayerId) -> bool,
+        visible_layers: Option<&std::collections::HashSet<LayerId>>,
+        keep_input: bool,
+    ) {
+        self.apply_with_areas(
+            context,
+            Some(&|x: &LayerId| owns_layer(*x)),
+            visible_layers,
+            keep_input,
+        );
+    }
+
+    /// Applies the snapshot to the current context. If `owns_layer` is provided, area
+    /// state is merged for the owned layers instead of being replaced. If `visible_layers`
+    /// is provided, the shapes of other layers are merged instead of being replaced.
+    fn apply_with_areas(
+        self,
+        context: &Context,
+        owns_layer: Option<&dyn Fn(&LayerId) -> bool>,
+        visible_layers: Option<&std::collections::HashSet<LayerId>>,
+        keep_input: bool,
+    ) {
         let Self::Created(value) = self else {
             panic!("Snapshot was not `Created` variant.")
         };
+
+        let label_selection_state = value.memory.label_selection_state.clone();
+        match owns_layer {
+            Some(owned) => merge_label_selection(context, label_selection_state, owned),
+            None => {
+                context.with_plugin(|x: &mut egui::text_selection::LabelSelectionState| {
+                    *x = label_selection_state.into()
+                });
+            }
+        }
+        drag::set_drag_payload(context, value.drag_payload);
+
         let exposed = private_hack::Context::from_context(context);
         let mut ctx = exposed.0.write();
 
@@ -165,6 +758,7 @@ impl CreateContextSnapshot {
             .unwrap_or(u64::MAX);
         let new_frame = frame_nr != value.deltas.frame_count;
         if let Some(style) = value.style {
+            let style = style.resolve(ctx.memory.options.style());
             match ctx.memory.options.theme() {
                 private_hack::Theme::Dark => {
                     ctx.memory.options.dark_style = style;
@@ -175,18 +769,35 @@ impl CreateContextSnapshot {
             }
         }
 
-        Self::apply_memory_snapshot(&mut ctx, value.memory);
+        Self::apply_memory_snapshot(&mut ctx, value.memory, owns_layer);
         Self::apply_options_snapshot(&mut ctx, &value.options);
         ctx.new_zoom_factor = value.new_zoom_factor;
         ctx.last_viewport = value.last_viewport;
-        Self::apply_viewport_snapshots(&mut ctx, &value.deltas, value.viewports);
-        ctx.memory.data.insert_temp(Id::NULL, value.deltas);
+        #[cfg(feature = "accesskit")]
+        {
+            ctx.is_accesskit_enabled = value.accesskit_enabled;
+        }
+        let applied_input = Self::apply_viewport_snapshots(
+            &mut ctx,
+            &value.deltas,
+            value.viewports,
+            visible_layers,
+            owns_layer.is_some(),
+            keep_input,
+        );
+        let mut deltas = value.deltas;
+        deltas.input_pass_count = if applied_input.complete {
+            deltas.pass_count
+        } else {
+            u64::MAX
+        };
+        ctx.memory.data.insert_temp(Id::NULL, deltas);
+        ctx.memory.data.insert_temp(Id::NULL, applied_input);
         let last_style = LastStyle(ctx.memory.options.style().clone());
         ctx.memory.data.insert_temp(Id::NULL, last_style);
 
         if let Some(font_definitions) = value.font_definitions {
-            let to_insert =
-                std::mem::replace(&mut ctx.memory.new_font_definitions, Some(font_definitions));
+            let to_insert = ctx.memory.new_font_definitions.replace(font_definitions);
             Self::update_fonts_mut(&mut ctx);
             ctx.memory.new_font_definitions = to_insert;
         } else if new_frame {
@@ -198,18 +809,21 @@ impl CreateContextSnapshot {
     }
 
     /// Updates the memory from the snapshot.
-    fn apply_memory_snapshot(ctx: &mut private_hack::ContextImpl, snapshot: MemorySnapshot) {
-        ctx.memory.data.insert_temp(
-            Id::new(ViewportId::ROOT),
-            egui::text_selection::LabelSelectionState::from(snapshot.label_selection_state),
-        );
+    fn apply_memory_snapshot(
+        ctx: &mut private_hack::ContextImpl,
+        snapshot: MemorySnapshot,
+        owns_layer: Option<&dyn Fn(&LayerId) -> bool>,
+    ) {
         ctx.memory.new_font_definitions = snapshot.new_font_definitions;
         ctx.memory.add_fonts = snapshot.add_fonts;
         ctx.memory.viewport_id = snapshot.viewport_id;
         ctx.memory.popups = snapshot.popups;
         ctx.memory.everything_is_visible = snapshot.everything_is_visible;
         ctx.memory.to_global = snapshot.to_global;
-        ctx.memory.areas = snapshot.areas;
+        match owns_layer {
+            Some(owned) => merge_areas(&mut ctx.memory.areas, snapshot.areas, owned),
+            None => ctx.memory.areas = snapshot.areas,
+        }
         ctx.memory.interactions = snapshot.interactions;
         ctx.memory.focus = snapshot.focus;
     }
@@ -229,32 +843,91 @@ impl CreateContextSnapshot {
         ctx.memory.options.reduce_texture_memory = snapshot.reduce_texture_memory;
     }
 
-    /// Updates the list of viewports from the snapshot list.
+    /// Updates the list of viewports from the snapshot list, rebuilding input from raw input
+    /// where the snapshot provides it. If `visible_layers` is provided, the snapshot's shapes
+    /// only replace those of the visible layers, and are added to those of other layers, whose
+    /// widgets and AccessKit nodes are kept. The snapshot's output events, which were all added
+    /// by the plugin, are added to the context's. If
+    /// `keep_clipboard` is set, the context's clipboard commands are kept, since they were
+    /// withheld from the plugin which produced the snapshot. If `keep_input` is set, the
+    /// context's input and widget hits are kept, since the snapshot's were redacted.
     fn apply_viewport_snapshots(
         ctx: &mut private_hack::ContextImpl,
         deltas: &ContextSnapshotDeltas,
         snapshots: ViewportIdMap<ViewportStateSnapshot>,
-    ) {
+        visible_layers: Option<&std::collections::HashSet<LayerId>>,
+        keep_clipboard: bool,
+        keep_input: bool,
+    ) -> AppliedInput {
+        let mut applied_input = AppliedInput {
+            complete: true,
+            replayed: false,
+        };
         ctx.viewports.retain(|x, _| snapshots.contains_key(x));
         for (id, snapshot) in snapshots {
             let viewport = ctx.viewports.get_mut(&id).expect("Failed to get viewport.");
             viewport.class = snapshot.class;
             viewport.builder = snapshot.builder;
-            viewport.input = snapshot.input;
-            viewport.this_pass = snapshot.this_pass;
-            viewport.prev_pass = snapshot.prev_pass;
+            match snapshot.input {
+                _ if keep_input => {}
+                ViewportInputSnapshot::Full { input, redacted } => {
+                    viewport.input = *input;
+                    applied_input.complete &= !redacted;
+                }
+                ViewportInputSnapshot::Raw {
+                    raw,
+                    requested_immediate_repaint_prev_pass,
+                    pixels_per_point,
+                    options,
+                } => {
+                    viewport.input = std::mem::take(&mut viewport.input).begin_pass(
+                        *raw,
+                        requested_immediate_repaint_prev_pass,
+                        pixels_per_point,
+                        options,
+                    );
+                    applied_input.replayed = true;
+                }
+                ViewportInputSnapshot::Unchanged => applied_input.replayed = true,
+            }
+            let mut this_pass = snapshot.this_pass;
+            let mut prev_pass = snapshot.prev_pass;
+            if let Some(layers) = visible_layers {
+                restore_hidden_pass_state(&mut this_pass, &viewport.this_pass, layers);
+                restore_hidden_pass_state(&mut prev_pass, &viewport.prev_pass, layers);
+            }
+            viewport.this_pass = this_pass;
+            viewport.prev_pass = prev_pass;
             viewport.used = snapshot.used;
-            viewport.hits = snapshot.hits;
+            if !keep_input {
+                viewport.hits = snapshot.hits;
+            }
             viewport.interact_widgets = snapshot.interact_widgets;
             viewport.repaint.cumulative_pass_nr = deltas.pass_count;
             viewport.repaint.cumulative_frame_nr = deltas.frame_count;
-            viewport.graphics = snapshot.graphics;
-            viewport.output = snapshot.output;
+            match visible_layers {
+                Some(layers) => merge_graphics(&mut viewport.graphics, snapshot.graphics, layers),
+                None => viewport.graphics = snapshot.graphics,
+            }
+            let mut output = snapshot.output;
+            if visible_layers.is_some() {
+                let mut events = std::mem::take(&mut viewport.output.events);
+                events.append(&mut output.events);
+                output.events = events;
+            }
+            if keep_clipboard {
+                let mut commands = std::mem::take(&mut viewport.output.commands);
+                commands.retain(is_clipboard_command);
+                commands.append(&mut output.commands);
+                output.commands = commands;
+            }
+            viewport.output = output;
             viewport.commands = snapshot.commands;
             viewport.num_multipass_in_row = snapshot.num_multipass_in_row;
         }
 
-        Self::reinitialize_galleys(ctx)
+        Self::reinitialize_galleys(ctx);
+        applied_input
     }
 
     /// Reloads all galleys from the cache, because galley data is not serialized
@@ -327,28 +1000,26 @@ impl Serialize for CreateContextSnapshot {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         match self {
             CreateContextSnapshot::FromContext(context, deltas) => {
-                let current_deltas = ContextSnapshotDeltas::from_context(context);
-                let exposed = private_hack::Context::from_context(context);
-                let ctx = exposed.0.read();
-
-                let style = (deltas.style_count != current_deltas.style_count)
-                    .then(|| ctx.memory.options.style().clone());
-
-                let font_definitions = (deltas.font_definitions_count
-                    != current_deltas.font_definitions_count)
-                    .then_some(&ctx.font_definitions);
-
-                let borrow = ContextShapshotBorrow {
-                    deltas: &current_deltas,
-                    font_definitions,
-                    memory: &ctx.memory,
-                    style,
-                    new_zoom_factor: &ctx.new_zoom_factor,
-                    last_viewport: &ctx.last_viewport,
-                    viewports: &ctx.viewports,
-                };
-                <ContextShapshotBorrow as Serialize>::serialize(&borrow, serializer)
+                Self::serialize_context(context, deltas, None, false, None, None, serializer)
             }
+            CreateContextSnapshot::Redacted(context, deltas, redaction) => Self::serialize_context(
+                context,
+                deltas,
+                Some(redaction),
+                false,
+                None,
+                None,
+                serializer,
+            ),
+            CreateContextSnapshot::Filtered(context, deltas, filter) => Self::serialize_context(
+                context,
+                deltas,
+                filter.redaction.as_ref(),
+                filter.replay_input,
+                filter.layers.as_ref(),
+                filter.owned_layers.as_ref(),
+                serializer,
+            ),
             CreateContextSnapshot::Created(_) => Err(serde::ser::Error::custom(
                 "Cannot serialize created snapshot",
             )),
@@ -356,14 +1027,500 @@ impl Serialize for CreateContextSnapshot {
     }
 }
 
+impl CreateContextSnapshot {
+    /// Serializes a snapshot of the given context, withholding any redacted input and the
+    /// shapes of layers outside of `layers`. If `replay` is set, the recipient rebuilds its
+    /// input from raw input where possible. If `owned_layers` is provided, the recipient is a
+    /// plugin which may not observe the clipboard or text selected in other layers.
+    fn serialize_context<S: Serializer>(
+        context: &Context,
+        deltas: &ContextSnapshotDeltas,
+        redaction: Option<&InputRedaction>,
+        replay: bool,
+        layers: Option<&std::collections::HashSet<LayerId>>,
+        owned_layers: Option<&std::collections::HashSet<LayerId>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let current_deltas = ContextSnapshotDeltas::from_context(context);
+        let label_selection_state = context
+            .with_plugin(|x: &mut egui::text_selection::LabelSelectionState| x.clone())
+            .unwrap_or_default();
+        let drag_payload = drag::drag_payload(context);
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        let style = (deltas.style_count != current_deltas.style_count)
+            .then(|| ctx.memory.options.style().clone());
+
+        let font_definitions = (deltas.font_definitions_count
+            != current_deltas.font_definitions_count)
+            .then_some(&ctx.font_definitions);
+
+        let borrow = ContextShapshotBorrow {
+            deltas: &current_deltas,
+            drag_payload,
+            font_definitions,
+            label_selection_state: private_hack::LabelSelectionState::from_label_selection_state(
+                &label_selection_state,
+            ),
+            memory: &ctx.memory,
+            style,
+            new_zoom_factor: &ctx.new_zoom_factor,
+            last_viewport: &ctx.last_viewport,
+            viewports: &ctx.viewports,
+            #[cfg(feature = "accesskit")]
+            accesskit_enabled: ctx.is_accesskit_enabled,
+            #[cfg(not(feature = "accesskit"))]
+            accesskit_enabled: false,
+            redaction,
+            layers,
+            owned_layers,
+            replay_pass: (replay && deltas.input_pass_count == deltas.pass_count)
+                .then_some(deltas.pass_count),
+        };
+        <ContextShapshotBorrow as Serialize>::serialize(&borrow, serializer)
+    }
+}
+
 impl<'de> Deserialize<'de> for CreateContextSnapshot {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        Ok(Self::Created(
+        Ok(Self::Created(Box::new(
             <ContextSnapshot as Deserialize>::deserialize(deserializer)?,
-        ))
+        )))
+    }
+}
+
+/// A context snapshot which is encoded before it crosses the WASM boundary, so that the
+/// number of bytes transferred is known to both the host and the plugin.
+#[doc(hidden)]
+pub struct EncodedSnapshot(Vec<u8>);
+
+impl EncodedSnapshot {
+    /// Encodes the given snapshot.
+    pub fn encode(snapshot: &CreateContextSnapshot) -> Self {
+        Self(bincode::serialize(snapshot).expect("Failed to encode context snapshot."))
+    }
+
+    /// Decodes the snapshot, producing the `Created` variant. Returns `None` if the
+    /// encoded snapshot is malformed.
+    pub fn decode(&self) -> Option<CreateContextSnapshot> {
+        bincode::deserialize(&self.0).ok()
+    }
+
+    /// Gets the number of bytes in the encoded snapshot.
+    pub fn encoded_len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Serialize for EncodedSnapshot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for EncodedSnapshot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(EncodedSnapshotVisitor)
+    }
+}
+
+#[cfg(test)]
+impl CreateContextSnapshot {
+    /// Sends the snapshot across a simulated WASM boundary, producing the `Created` variant.
+    pub(crate) fn transfer(&self) -> Self {
+        EncodedSnapshot::encode(self)
+            .decode()
+            .expect("Failed to decode context snapshot.")
+    }
+}
+
+/// Reads the bytes of an [`EncodedSnapshot`].
+struct EncodedSnapshotVisitor;
+
+impl<'de> serde::de::Visitor<'de> for EncodedSnapshotVisitor {
+    type Value = EncodedSnapshot;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("an encoded context snapshot")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(EncodedSnapshot(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(EncodedSnapshot(v))
     }
 }
 
 /// Tracks the last style that was applied.
 #[derive(Clone)]
 struct LastStyle(Arc<private_hack::Style>);
+
+/// Describes the input which was applied from the last snapshot.
+#[derive(Clone, Copy, Default)]
+struct AppliedInput {
+    /// Whether the input of every viewport was received without redaction.
+    complete: bool,
+    /// Whether input was rebuilt from raw input or was already held, rather than being
+    /// received in full.
+    replayed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_snapshot_round_trip() {
+        let host = Context::default();
+        host.options_mut(|x| x.zoom_with_keyboard = false);
+        let _ = host.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| ui.label("Hello"));
+        });
+
+        let snapshot = EncodedSnapshot::encode(&CreateContextSnapshot::FromContext(
+            host.clone(),
+            ContextSnapshotDeltas::default(),
+        ));
+        assert_eq!(
+            bincode::serialize(&snapshot)
+                .expect("Failed to encode snapshot.")
+                .len(),
+            snapshot.encoded_len() + size_of::<u64>()
+        );
+
+        let plugin = Context::default();
+        snapshot
+            .decode()
+            .expect("Failed to decode snapshot.")
+            .apply(&plugin);
+        assert!(!plugin.options(|x| x.zoom_with_keyboard));
+        assert_eq!(plugin.cumulative_frame_nr(), host.cumulative_frame_nr());
+    }
+
+    #[test]
+    fn malformed_snapshot_is_rejected() {
+        let snapshot = EncodedSnapshot::encode(&CreateContextSnapshot::FromContext(
+            Context::default(),
+            ContextSnapshotDeltas::default(),
+        ));
+        let truncated = EncodedSnapshot(snapshot.0[..snapshot.encoded_len() / 2].to_vec());
+        assert!(truncated.decode().is_none());
+    }
+
+    /// Runs a plugin that adds a labelled button to its own area, starting from the host
+    /// state, and applies the plugin's edits to the host. Returns the button's ID.
+    #[cfg(feature = "accesskit")]
+    fn run_button_plugin(host: &Context, plugin: &Context, label: &str) -> Id {
+        CreateContextSnapshot::FromContext(host.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply(plugin);
+        let button = Area::new(Id::new(label))
+            .show(plugin, |ui| ui.button(label).id)
+            .inner;
+        CreateContextSnapshot::FromContext(plugin.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(host, |_| true, None, false);
+        button
+    }
+
+    #[cfg(feature = "accesskit")]
+    #[test]
+    fn plugin_accesskit_nodes_reach_host() {
+        let host = Context::default();
+        host.enable_accesskit();
+        host.begin_pass(RawInput::default());
+        let button = run_button_plugin(&host, &Context::default(), "Plugin button");
+
+        let update = host
+            .end_pass()
+            .platform_output
+            .accesskit_update
+            .expect("AccessKit was off.");
+        let (_, node) = update
+            .nodes
+            .iter()
+            .find(|(id, _)| *id == egui::accesskit::NodeId::from(button.value()))
+            .expect("The plugin's button was not in the host's tree.");
+        assert_eq!(node.role(), egui::accesskit::Role::Button);
+        assert_eq!(node.label(), Some("Plugin button"));
+    }
+
+    #[cfg(feature = "accesskit")]
+    #[test]
+    fn plugins_follow_host_with_accesskit_disabled() {
+        let host = Context::default();
+        host.begin_pass(RawInput::default());
+        let plugin = Context::default();
+        plugin.enable_accesskit();
+        run_button_plugin(&host, &plugin, "Plugin button");
+
+        let exposed = private_hack::Context::from_context(&plugin);
+        assert!(exposed.0.read().viewports[&ViewportId::ROOT]
+            .this_pass
+            .accesskit_state
+            .is_none());
+        assert!(host.end_pass().platform_output.accesskit_update.is_none());
+    }
+
+    #[test]
+    fn animations_request_repaints() {
+        let ctx = Context::default();
+        ctx.begin_pass(RawInput::default());
+        let id = Id::new("animation");
+        ctx.animate_bool(id, false);
+        repaint::reset(&ctx);
+        assert!(repaint::requests(&ctx).is_empty());
+
+        ctx.animate_bool(id, true);
+        assert_eq!(
+            repaint::requests(&ctx).get(&ViewportId::ROOT),
+            Some(&std::time::Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn plugins_only_reorder_their_own_windows() {
+        let host = Context::default();
+        let show_area = |ctx: &Context, name: &str| {
+            Area::new(Id::new(name))
+                .show(ctx, |ui| ui.label(name))
+                .response
+                .layer_id
+        };
+        let _ = host.run(RawInput::default(), |ctx| {
+            show_area(ctx, "first");
+            show_area(ctx, "second");
+        });
+        host.begin_pass(RawInput::default());
+        let first = show_area(&host, "first");
+        let second = show_area(&host, "second");
+
+        let plugin = Context::default();
+        CreateContextSnapshot::FromContext(host.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply(&plugin);
+        let own = show_area(&plugin, "plugin");
+        plugin.move_to_top(first);
+        plugin.move_to_top(own);
+        CreateContextSnapshot::FromContext(plugin, ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |x| x == own, None, false);
+
+        {
+            let exposed = private_hack::Context::from_context(&host);
+            let ctx = exposed.0.read();
+            let areas = &ctx.memory.areas[&ViewportId::ROOT];
+            let windows = areas.order.iter().filter(|x| x.order == Order::Middle);
+            assert!(windows.eq(&[first, second, own]));
+            assert!(areas.wants_to_be_on_top.contains(&own));
+            assert!(!areas.wants_to_be_on_top.contains(&first));
+        }
+
+        let _ = host.end_pass();
+        let windows = host.memory(|x| {
+            x.layer_ids()
+                .filter(|x| x.order == Order::Middle)
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(windows, [first, second, own]);
+    }
+
+    #[test]
+    fn painted_text_is_laid_out_by_the_host() {
+        let layer = LayerId::new(Order::Foreground, Id::new("overlay"));
+        let square = Rect::from_min_size(Pos2::ZERO, vec2(10.0, 10.0));
+        let paint = LayerPaint::new(
+            layer,
+            vec![
+                Shape::rect_filled(square, 0.0, Color32::RED).into(),
+                PaintShape::text(
+                    pos2(50.0, 50.0),
+                    Align2::CENTER_CENTER,
+                    "Overlay",
+                    FontId::default(),
+                    Color32::WHITE,
+                ),
+            ],
+        );
+        let paint: LayerPaint =
+            bincode::deserialize(&bincode::serialize(&paint).expect("Failed to encode paint."))
+                .expect("Failed to decode paint.");
+
+        let host = Context::default();
+        let output = host.run(RawInput::default(), |ctx| paint.clone().paint(ctx));
+        let [rect, text] = &output.shapes[..] else {
+            panic!("Expected two shapes.");
+        };
+        assert!(matches!(&rect.shape, Shape::Rect(x) if x.rect == square));
+        let Shape::Text(text) = &text.shape else {
+            panic!("Expected text.");
+        };
+        assert_eq!(text.galley.text(), "Overlay");
+        assert_eq!(text.galley.job.sections[0].format.color, Color32::WHITE);
+        let rect = Align2::CENTER_CENTER.anchor_size(pos2(50.0, 50.0), text.galley.size());
+        assert_eq!(text.pos, rect.min);
+    }
+
+    #[test]
+    fn replayed_input_matches_host_input() {
+        let host = Context::default();
+        let plugin = Context::default();
+        plugin.begin_pass(RawInput::default());
+        let transfer = |replay_input| {
+            let deltas = ContextSnapshotDeltas::from_context(&plugin);
+            let filter = SnapshotFilter {
+                replay_input,
+                ..Default::default()
+            };
+            let snapshot = EncodedSnapshot::encode(&CreateContextSnapshot::Filtered(
+                host.clone(),
+                deltas,
+                filter,
+            ));
+            let len = snapshot.encoded_len();
+            snapshot
+                .decode()
+                .expect("Failed to decode snapshot.")
+                .apply(&plugin);
+            let applied = plugin.data(|x| x.get_temp::<AppliedInput>(Id::NULL));
+            (len, applied.is_some_and(|x| x.replayed))
+        };
+
+        host.begin_pass(RawInput::default());
+        assert!(!transfer(true).1);
+        let _ = host.end_pass();
+
+        host.begin_pass(RawInput {
+            events: vec![
+                Event::PointerMoved(pos2(30.0, 40.0)),
+                Event::Key {
+                    key: Key::A,
+                    physical_key: None,
+                    pressed: true,
+                    repeat: false,
+                    modifiers: Modifiers::default(),
+                },
+            ],
+            ..Default::default()
+        });
+        let (replayed_len, replayed) = transfer(true);
+        assert!(replayed);
+        assert_eq!(plugin.pointer_hover_pos(), Some(pos2(30.0, 40.0)));
+        assert!(plugin.input(|x| x.key_pressed(Key::A)));
+        assert_eq!(
+            plugin.input(|x| x.events.clone()),
+            host.input(|x| x.events.clone())
+        );
+
+        let (unchanged_len, unchanged) = transfer(true);
+        assert!(unchanged);
+        assert!(unchanged_len < replayed_len);
+        assert!(plugin.input(|x| x.key_pressed(Key::A)));
+
+        let (full_len, full) = transfer(false);
+        assert!(!full);
+        assert!(replayed_len < full_len);
+    }
+
+    /// Selects the given text within a label on the layer, as if the user had dragged
+    /// across it.
+    fn select_text(context: &Context, layer_id: LayerId, text: &str) {
+        let cursor = |index| private_hack::WidgetTextCursor {
+            widget_id: Id::new(text),
+            ccursor: egui::text::CCursor::new(index),
+            pos: Pos2::ZERO,
+        };
+        let mut state =
+            private_hack::LabelSelectionState::from_label_selection_state(&Default::default())
+                .clone();
+        state.selection = Some(private_hack::CurrentSelection {
+            layer_id,
+            primary: cursor(0),
+            secondary: cursor(text.len()),
+        });
+        state.text_to_copy = text.to_owned();
+        context.with_plugin(|x: &mut egui::text_selection::LabelSelectionState| {
+            *x = state.into();
+        });
+    }
+
+    /// Gets the selected text and copied text of a context.
+    fn copied_text(context: &Context) -> (String, Vec<String>) {
+        let selected = context
+            .with_plugin(|x: &mut egui::text_selection::LabelSelectionState| {
+                private_hack::LabelSelectionState::from_label_selection_state(x)
+                    .text_to_copy
+                    .clone()
+            })
+            .unwrap_or_default();
+        let copied = context.output(|x| {
+            x.commands
+                .iter()
+                .filter_map(|x| match x {
+                    OutputCommand::CopyText(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        });
+        (selected, copied)
+    }
+
+    #[test]
+    fn selected_and_copied_text_stay_with_their_owner() {
+        let host_layer = LayerId::new(Order::Middle, Id::new("host"));
+        let plugin_layer = LayerId::new(Order::Middle, Id::new("plugin"));
+        let host = Context::default();
+        host.begin_pass(RawInput::default());
+        select_text(&host, host_layer, "secret");
+        host.copy_text("secret".to_owned());
+
+        let send_to_plugin = |layer| {
+            let plugin = Context::default();
+            let filter = SnapshotFilter {
+                owned_layers: Some(std::collections::HashSet::from([layer])),
+                ..Default::default()
+            };
+            CreateContextSnapshot::Filtered(host.clone(), ContextSnapshotDeltas::default(), filter)
+                .transfer()
+                .apply(&plugin);
+            plugin
+        };
+        assert_eq!(copied_text(&send_to_plugin(host_layer)).0, "secret");
+        let plugin = send_to_plugin(plugin_layer);
+        assert_eq!(copied_text(&plugin), (String::new(), Vec::new()));
+
+        plugin.copy_text("plugin".to_owned());
+        CreateContextSnapshot::FromContext(plugin, ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |x| x == plugin_layer, None, false);
+        let (selected, copied) = copied_text(&host);
+        assert_eq!(selected, "secret");
+        assert_eq!(copied, ["secret", "plugin"]);
+    }
+
+    #[test]
+    fn debug_options_and_named_formatters_reach_plugins() {
+        let percent = egui::style::NumberFormatter::new(|x, _| format!("{}%", x * 100.0));
+        register_number_formatter("percent", percent);
+        let host = Context::default();
+        host.style_mut(|x| {
+            x.debug.debug_on_hover = true;
+            x.debug.show_widget_hits = true;
+            x.number_formatter =
+                number_formatter("percent").expect("Formatter was not registered.");
+        });
+        let _ = host.run(RawInput::default(), |_| {});
+
+        let plugin = Context::default();
+        CreateContextSnapshot::FromContext(host.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply(&plugin);
+        let style = plugin.style();
+        assert!(style.debug.debug_on_hover);
+        assert!(style.debug.show_widget_hits);
+        assert_eq!(style.number_formatter.format(0.5, 0..=2), "50%");
+    }
+}
@@ -0,0 +1,609 @@
+use crate::*;
+use egui::load::*;
+use std::collections::*;
+use std::hash::Hash;
+
+/// The loaders which forward requests to the host, keyed by the name of the host context.
+/// Images belong to a single host context, so each context has its own loaders.
+static HOST_LOADERS: Mutex<BTreeMap<String, Arc<HostLoaders>>> = Mutex::new(BTreeMap::new());
+
+/// Installs the host loaders for the named host context into the given context. The image
+/// and texture loaders take precedence over any loaders that were previously installed, while
+/// the bytes loader is only used for URIs that no other bytes loader supports.
+pub(crate) fn install(context: &Context, name: &str) {
+    let loaders = host_loaders().entry(name.to_owned()).or_default().clone();
+    context
+        .loaders()
+        .bytes
+        .lock()
+        .insert(0, loaders.bytes.clone());
+    context.add_image_loader(loaders.image.clone());
+    context.add_texture_loader(loaders.texture.clone());
+}
+
+/// Forwards all requests made during the current transaction to the host,
+/// and stores the results so that they are available during the next transaction.
+pub(crate) fn flush(ctx: &dyn Egui, plugin: PluginId, name: &str) {
+    let Some(loaders) = host_loaders().get(name).cloned() else {
+        return;
+    };
+
+    loaders
+        .bytes
+        .forwarded
+        .flush(|x| ctx.load_bytes(plugin, name.to_owned(), x));
+    loaders
+        .image
+        .forwarded
+        .flush(|x| ctx.load_images(plugin, name.to_owned(), x));
+    loaders
+        .texture
+        .forwarded
+        .flush(|x| ctx.load_textures(plugin, name.to_owned(), x));
+}
+
+/// Locks the host loaders.
+fn host_loaders() -> MutexGuard<'static, BTreeMap<String, Arc<HostLoaders>>> {
+    HOST_LOADERS.lock().expect("Failed to lock host loaders.")
+}
+
+/// Uniquely identifies an image that was requested by a guest.
+type ImageKey = (String, SizeHint);
+
+/// Uniquely identifies a texture that was requested by a guest.
+type TextureKey = (String, TextureOptions, SizeHint);
+
+/// The loaders which forward the requests of a single context to the host.
+struct HostLoaders {
+    /// Forwards requests for bytes.
+    bytes: Arc<HostBytesLoader>,
+    /// Forwards requests for decoded images.
+    image: Arc<HostImageLoader>,
+    /// Forwards requests for textures.
+    texture: Arc<HostTextureLoader>,
+}
+
+impl Default for HostLoaders {
+    fn default() -> Self {
+        let included = Arc::<IncludedBytes>::default();
+        Self {
+            bytes: Arc::default(),
+            image: Arc::new(HostImageLoader {
+                forwarded: Forwarded::default(),
+                included: included.clone(),
+            }),
+            texture: Arc::new(HostTextureLoader {
+                forwarded: Forwarded::default(),
+                included,
+            }),
+        }
+    }
+}
+
+/// The requests of one kind which will be sent to the host, and the host's responses to them.
+struct Forwarded<K, Q, R> {
+    /// The most recent host response for each key that has been requested.
+    responses: Mutex<HashMap<K, R>>,
+    /// The requests which will be sent to the host at the end of the transaction.
+    requests: Mutex<Vec<(K, Q)>>,
+}
+
+impl<K: Clone + Eq + Hash, Q, R: Clone> Forwarded<K, Q, R> {
+    /// Gets the most recent host response for the given key.
+    fn response(&self, key: &K) -> Option<R> {
+        self.responses().get(key).cloned()
+    }
+
+    /// Queues a request for the given key, unless one was already queued during this transaction.
+    fn request(&self, key: K, request: impl FnOnce() -> Q) {
+        let mut requests = self.requests();
+        if !requests.iter().any(|(x, _)| *x == key) {
+            requests.push((key, request()));
+        }
+    }
+
+    /// Sends the queued requests to the host with the given function, and stores the responses.
+    fn flush(&self, load: impl FnOnce(Vec<Q>) -> Vec<R>) {
+        let requests = std::mem::take(&mut *self.requests());
+        if requests.is_empty() {
+            return;
+        }
+
+        let (keys, requests) = requests.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+        let responses = load(requests);
+        self.responses().extend(keys.into_iter().zip(responses));
+    }
+
+    /// Forgets the responses for which the predicate returns `true`.
+    fn forget(&self, predicate: impl Fn(&K) -> bool) {
+        self.responses().retain(|x, _| !predicate(x));
+    }
+
+    /// Locks the map of host responses.
+    fn responses(&self) -> MutexGuard<'_, HashMap<K, R>> {
+        self.responses
+            .lock()
+            .expect("Failed to lock host loader responses.")
+    }
+
+    /// Locks the list of outstanding requests.
+    fn requests(&self) -> MutexGuard<'_, Vec<(K, Q)>> {
+        self.requests
+            .lock()
+            .expect("Failed to lock host loader requests.")
+    }
+}
+
+impl<K, Q, R> Default for Forwarded<K, Q, R> {
+    fn default() -> Self {
+        Self {
+            responses: Mutex::new(HashMap::new()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// Tracks which of the bytes included on the guest have been sent to the host.
+#[derive(Default)]
+struct IncludedBytes(Mutex<HashSet<String>>);
+
+impl IncludedBytes {
+    /// Determines where the host should find the image with the given URI. Bytes that were
+    /// included on the guest (such as those from `include_image!`) cannot be accessed by the
+    /// host, so they are sent along with the first request for them.
+    fn source(&self, ctx: &Context, uri: &str) -> UriSource {
+        let mut sent = self.0.lock().expect("Failed to lock included bytes.");
+        if sent.contains(uri) {
+            return UriSource::Included { bytes: None };
+        }
+
+        match ctx.loaders().include.load(ctx, uri) {
+            Ok(BytesPoll::Ready { bytes, .. }) => {
+                sent.insert(uri.to_owned());
+                UriSource::Included {
+                    bytes: Some(bytes.to_vec()),
+                }
+            }
+            _ => UriSource::Host,
+        }
+    }
+
+    /// Forgets that the bytes with the given URI were sent, so that they are sent again if
+    /// they are included anew.
+    fn forget(&self, uri: &str) {
+        self.0
+            .lock()
+            .expect("Failed to lock included bytes.")
+            .remove(uri);
+    }
+
+    /// Forgets that any bytes were sent.
+    fn forget_all(&self) {
+        self.0
+            .lock()
+            .expect("Failed to lock included bytes.")
+            .clear();
+    }
+}
+
+/// A bytes loader which resolves URIs using the loaders installed on the host context.
+/// Requests are batched and sent to the host at the end of each transaction.
+#[derive(Default)]
+struct HostBytesLoader {
+    /// The requests and responses of this loader.
+    forwarded: Forwarded<String, BytesRequest, BytesResponse>,
+}
+
+impl BytesLoader for HostBytesLoader {
+    fn id(&self) -> &str {
+        generate_loader_id!(HostBytesLoader)
+    }
+
+    fn load(&self, _: &Context, uri: &str) -> BytesLoadResult {
+        let size = match self.forwarded.response(&uri.to_owned()) {
+            Some(BytesResponse::Ready { size, bytes, mime }) => {
+                return Ok(BytesPoll::Ready {
+                    size,
+                    bytes: Bytes::Shared(bytes.into()),
+                    mime,
+                })
+            }
+            Some(BytesResponse::Failed(error)) => return Err(LoadError::Loading(error)),
+            Some(BytesResponse::Pending { size }) => size,
+            None => None,
+        };
+
+        self.forwarded.request(uri.to_owned(), || BytesRequest {
+            uri: uri.to_owned(),
+        });
+        Ok(BytesPoll::Pending { size })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.forwarded.forget(|x| x == uri);
+    }
+
+    fn forget_all(&self) {
+        self.forwarded.forget(|_| true);
+    }
+
+    fn byte_size(&self) -> usize {
+        // Bytes are loaded by the host.
+        0
+    }
+}
+
+/// An image loader which decodes images using the loaders installed on the host context.
+/// Requests are batched and sent to the host at the end of each transaction.
+struct HostImageLoader {
+    /// The requests and responses of this loader.
+    forwarded: Forwarded<ImageKey, ImageRequest, ImageResponse>,
+    /// The bytes included on the guest which were sent to the host.
+    included: Arc<IncludedBytes>,
+}
+
+impl ImageLoader for HostImageLoader {
+    fn id(&self) -> &str {
+        generate_loader_id!(HostImageLoader)
+    }
+
+    fn load(&self, ctx: &Context, uri: &str, size_hint: SizeHint) -> ImageLoadResult {
+        let key = (uri.to_owned(), size_hint);
+        let size = match self.forwarded.response(&key) {
+            Some(ImageResponse::Ready(image)) => {
+                return Ok(ImagePoll::Ready {
+                    image: Arc::new(image),
+                })
+            }
+            Some(ImageResponse::Failed(error)) => return Err(LoadError::Loading(error)),
+            Some(ImageResponse::Pending { size }) => size,
+            None => None,
+        };
+
+        self.forwarded.request(key, || ImageRequest {
+            uri: uri.to_owned(),
+            size_hint: size_hint.into(),
+            source: self.included.source(ctx, uri),
+        });
+        Ok(ImagePoll::Pending { size })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.forwarded.forget(|(x, _)| x == uri);
+        self.included.forget(uri);
+    }
+
+    fn forget_all(&self) {
+        self.forwarded.forget(|_| true);
+        self.included.forget_all();
+    }
+
+    fn byte_size(&self) -> usize {
+        // Decoded images are owned by the host.
+        0
+    }
+}
+
+/// A texture loader which resolves textures using the loaders installed on the host context.
+/// Requests are batched and sent to the host at the end of each transaction.
+struct HostTextureLoader {
+    /// The requests and responses of this loader.
+    forwarded: Forwarded<TextureKey, TextureRequest, TextureResponse>,
+    /// The bytes included on the guest which were sent to the host.
+    included: Arc<IncludedBytes>,
+}
+
+impl TextureLoader for HostTextureLoader {
+    fn id(&self) -> &str {
+        generate_loader_id!(HostTextureLoader)
+    }
+
+    fn load(
+        &self,
+        ctx: &Context,
+        uri: &str,
+        texture_options: TextureOptions,
+        size_hint: SizeHint,
+    ) -> TextureLoadResult {
+        let key = (uri.to_owned(), texture_options, size_hint);
+        let size = match self.forwarded.response(&key) {
+            Some(TextureResponse::Ready { id, size }) => {
+                return Ok(TexturePoll::Ready {
+                    texture: SizedTexture::new(id, size),
+                })
+            }
+            Some(TextureResponse::Failed(error)) => return Err(LoadError::Loading(error)),
+            Some(TextureResponse::Pending { size }) => size,
+            None => None,
+        };
+
+        self.forwarded.request(key, || TextureRequest {
+            uri: uri.to_owned(),
+            texture_options,
+            size_hint: size_hint.into(),
+            source: self.included.source(ctx, uri),
+        });
+        Ok(TexturePoll::Pending { size })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.forwarded.forget(|(x, _, _)| x == uri);
+        self.included.forget(uri);
+    }
+
+    fn forget_all(&self) {
+        self.forwarded.forget(|_| true);
+        self.included.forget_all();
+    }
+
+    fn byte_size(&self) -> usize {
+        // Texture memory is owned by the host.
+        0
+    }
+}
+
+/// Describes where the host should find the data for a URI that a plugin requested.
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum UriSource {
+    /// The URI is resolved by the loaders installed on the host context.
+    Host,
+    /// The URI refers to bytes which were included on the guest.
+    Included {
+        /// The included bytes, if they were not sent with an earlier request.
+        bytes: Option<Vec<u8>>,
+    },
+}
+
+/// Asks the host to load the bytes of a URI on behalf of a plugin.
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BytesRequest {
+    /// The URI to load.
+    pub uri: String,
+}
+
+/// The result of a [`BytesRequest`].
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BytesResponse {
+    /// The bytes are still loading.
+    Pending {
+        /// The size of the image, if known.
+        size: Option<Vec2>,
+    },
+    /// The bytes have been loaded.
+    Ready {
+        /// The size of the image, if known.
+        size: Option<Vec2>,
+        /// The contents of the URI.
+        bytes: Vec<u8>,
+        /// The MIME type of the contents, if known.
+        mime: Option<String>,
+    },
+    /// The bytes could not be loaded.
+    Failed(String),
+}
+
+/// Asks the host to decode an image on behalf of a plugin.
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageRequest {
+    /// The URI of the image.
+    pub uri: String,
+    /// The resolution at which the image should be rasterized.
+    pub size_hint: SizeHintSnapshot,
+    /// Where the host should find the image data.
+    pub source: UriSource,
+}
+
+/// The result of an [`ImageRequest`].
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ImageResponse {
+    /// The image is still loading.
+    Pending {
+        /// The size of the image, if known.
+        size: Option<Vec2>,
+    },
+    /// The image has been decoded.
+    Ready(ColorImage),
+    /// The image could not be loaded.
+    Failed(String),
+}
+
+/// Asks the host to load a texture on behalf of a plugin.
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TextureRequest {
+    /// The URI of the texture.
+    pub uri: String,
+    /// The options with which the texture should be sampled.
+    pub texture_options: TextureOptions,
+    /// The resolution at which the image should be rasterized.
+    pub size_hint: SizeHintSnapshot,
+    /// Where the host should find the image data.
+    pub source: UriSource,
+}
+
+/// The result of a [`TextureRequest`].
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TextureResponse {
+    /// The texture is still loading.
+    Pending {
+        /// The size of the image, if known.
+        size: Option<Vec2>,
+    },
+    /// The texture has been allocated on the host.
+    Ready {
+        /// The host texture.
+        id: TextureId,
+        /// The size of the image.
+        size: Vec2,
+    },
+    /// The texture could not be loaded.
+    Failed(String),
+}
+
+/// A serializable version of [`SizeHint`].
+#[doc(hidden)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum SizeHintSnapshot {
+    /// Scale the original size by some factor.
+    Scale(f32),
+    /// Scale to exactly this pixel width.
+    Width(u32),
+    /// Scale to exactly this pixel height.
+    Height(u32),
+    /// Scale to this pixel size.
+    Size {
+        /// The pixel width.
+        width: u32,
+        /// The pixel height.
+        height: u32,
+        /// Whether the image should fit within the size while keeping its aspect ratio.
+        maintain_aspect_ratio: bool,
+    },
+}
+
+impl From<SizeHint> for SizeHintSnapshot {
+    fn from(value: SizeHint) -> Self {
+        match value {
+            SizeHint::Scale(scale) => Self::Scale(scale.into_inner()),
+            SizeHint::Width(width) => Self::Width(width),
+            SizeHint::Height(height) => Self::Height(height),
+            SizeHint::Size {
+                width,
+                height,
+                maintain_aspect_ratio,
+            } => Self::Size {
+                width,
+                height,
+                maintain_aspect_ratio,
+            },
+        }
+    }
+}
+
+impl From<SizeHintSnapshot> for SizeHint {
+    fn from(value: SizeHintSnapshot) -> Self {
+        match value {
+            SizeHintSnapshot::Scale(scale) => Self::Scale(emath::OrderedFloat(scale)),
+            SizeHintSnapshot::Width(width) => Self::Width(width),
+            SizeHintSnapshot::Height(height) => Self::Height(height),
+            SizeHintSnapshot::Size {
+                width,
+                height,
+                maintain_aspect_ratio,
+            } => Self::Size {
+                width,
+                height,
+                maintain_aspect_ratio,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends the queued texture requests of the loaders to a host that reports every texture
+    /// as pending, and returns the requests.
+    fn flush_textures(loaders: &HostLoaders) -> Vec<TextureRequest> {
+        let mut sent = Vec::new();
+        loaders.texture.forwarded.flush(|requests| {
+            sent = requests.clone();
+            requests
+                .iter()
+                .map(|_| TextureResponse::Pending { size: None })
+                .collect()
+        });
+        sent
+    }
+
+    #[test]
+    fn included_bytes_are_sent_once() {
+        let ctx = Context::default();
+        ctx.include_bytes("bytes://icon.png", vec![1, 2, 3]);
+        let loaders = HostLoaders::default();
+        let load = || {
+            loaders.texture.load(
+                &ctx,
+                "bytes://icon.png",
+                TextureOptions::default(),
+                SizeHint::default(),
+            )
+        };
+
+        assert!(matches!(load(), Ok(TexturePoll::Pending { .. })));
+        let first = flush_textures(&loaders);
+        assert!(matches!(
+            first[..],
+            [TextureRequest {
+                source: UriSource::Included { bytes: Some(ref x) },
+                ..
+            }] if x == &[1, 2, 3]
+        ));
+
+        assert!(matches!(load(), Ok(TexturePoll::Pending { .. })));
+        let retry = flush_textures(&loaders);
+        assert!(matches!(
+            retry[..],
+            [TextureRequest {
+                source: UriSource::Included { bytes: None },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn other_uris_are_loaded_by_the_host() {
+        let ctx = Context::default();
+        let loaders = HostLoaders::default();
+        let _ = loaders.texture.load(
+            &ctx,
+            "file://icon.png",
+            TextureOptions::default(),
+            SizeHint::default(),
+        );
+        assert!(matches!(
+            flush_textures(&loaders)[..],
+            [TextureRequest {
+                source: UriSource::Host,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn bytes_are_forwarded_to_the_host() {
+        let ctx = Context::default();
+        install(&ctx, "bytes_are_forwarded_to_the_host");
+        ctx.include_bytes("bytes://included.txt", vec![1]);
+        assert!(matches!(
+            ctx.try_load_bytes("bytes://included.txt"),
+            Ok(BytesPoll::Ready { .. })
+        ));
+        assert!(matches!(
+            ctx.try_load_bytes("file://host.txt"),
+            Ok(BytesPoll::Pending { .. })
+        ));
+
+        let loaders = host_loaders()["bytes_are_forwarded_to_the_host"].clone();
+        loaders.bytes.forwarded.flush(|requests| {
+            assert_eq!(requests.len(), 1);
+            assert_eq!(requests[0].uri, "file://host.txt");
+            vec![BytesResponse::Ready {
+                size: None,
+                bytes: vec![4, 5],
+                mime: None,
+            }]
+        });
+        let Ok(BytesPoll::Ready { bytes, .. }) = ctx.try_load_bytes("file://host.txt") else {
+            panic!("Bytes were not loaded.");
+        };
+        assert_eq!(bytes.as_ref(), &[4, 5]);
+    }
+}
@@ -0,0 +1,562 @@
+use crate::*;
+use std::collections::*;
+
+/// Describes the content of a context at the beginning of a transaction, so that the
+/// content which a plugin added may be identified and merged into the host context.
+#[doc(hidden)]
+#[derive(Clone, Default)]
+pub struct ContextBase {
+    /// The layers which had area state.
+    areas: HashSet<LayerId>,
+    /// The number of fonts which were waiting to be added.
+    add_fonts: usize,
+    /// The shared drag-and-drop payload.
+    drag_payload: Option<Arc<DragPayload>>,
+    /// The content of each viewport.
+    viewports: ViewportIdMap<ViewportBase>,
+}
+
+impl ContextBase {
+    /// Records the content of the given context. If `visible_layers` is provided, the plugin
+    /// only receives the shapes of those layers and no output events, so all of its shapes on
+    /// other layers and all of its output events are new.
+    pub fn capture(context: &Context, visible_layers: Option<&HashSet<LayerId>>) -> Self {
+        let drag_payload = drag::drag_payload(context);
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        let areas = ctx
+            .memory
+            .areas
+            .values()
+            .flat_map(|x| x.order.iter().copied())
+            .collect();
+
+        let viewports = ctx
+            .viewports
+            .iter()
+            .map(|(id, viewport)| {
+                let mut shapes = HashMap::new();
+                for (order, paint_lists) in ORDERS.iter().zip(viewport.graphics.as_inner()) {
+                    for (layer, paint_list) in paint_lists {
+                        let layer = LayerId::new(*order, *layer);
+                        if visible_layers.is_none_or(|x| x.contains(&layer)) {
+                            shapes.insert(layer, paint_list.as_inner().len());
+                        }
+                    }
+                }
+
+                let base = ViewportBase {
+                    shapes,
+                    widgets: viewport.this_pass.widgets.by_id.keys().copied().collect(),
+                    #[cfg(feature = "accesskit")]
+                    accesskit_nodes: viewport
+                        .this_pass
+                        .accesskit_state
+                        .iter()
+                        .flat_map(|x| x.nodes.keys().chain(x.parent_map.keys()))
+                        .copied()
+                        .collect(),
+                    output_commands: viewport
+                        .output
+                        .commands
+                        .iter()
+                        .filter(|x| !is_clipboard_command(x))
+                        .count(),
+                    output_events: visible_layers.map_or(viewport.output.events.len(), |_| 0),
+                    cursor_icon: viewport.output.cursor_icon,
+                    ime: viewport.output.ime,
+                    commands: viewport.commands.len(),
+                    focused: ctx
+                        .memory
+                        .focus
+                        .get(id)
+                        .and_then(|x| x.focused_widget.as_ref())
+                        .map(|x| x.id),
+                    interaction: ctx
+                        .memory
+                        .interactions
+                        .get(id)
+                        .map(|x| (x.potential_click_id, x.potential_drag_id))
+                        .unwrap_or_default(),
+                    popup: ctx.memory.popups.get(id).map(|x| x.id),
+                };
+
+                (*id, base)
+            })
+            .collect();
+
+        Self {
+            areas,
+            add_fonts: ctx.memory.add_fonts.len(),
+            drag_payload,
+            viewports,
+        }
+    }
+}
+
+/// Describes the content of a single viewport at the beginning of a transaction.
+#[derive(Clone, Default)]
+struct ViewportBase {
+    /// The number of shapes in each layer.
+    shapes: HashMap<LayerId, usize>,
+    /// The widgets which had been added.
+    widgets: private_hack::IdSet,
+    /// The nodes which had been added to the AccessKit tree.
+    #[cfg(feature = "accesskit")]
+    accesskit_nodes: private_hack::IdSet,
+    /// The number of platform output commands that were sent to the plugin.
+    output_commands: usize,
+    /// The number of platform output events that were sent to the plugin.
+    output_events: usize,
+    /// The requested cursor icon.
+    cursor_icon: CursorIcon,
+    /// The requested IME state.
+    ime: Option<output::IMEOutput>,
+    /// The number of viewport commands.
+    commands: usize,
+    /// The focused widget.
+    focused: Option<Id>,
+    /// The widgets which could be clicked or dragged.
+    interaction: (Option<Id>, Option<Id>),
+    /// The open popup.
+    popup: Option<Id>,
+}
+
+impl CreateContextSnapshot {
+    /// Merges the content that a plugin added since `base` was captured into the context, rather
+    /// than replacing the context state. This allows multiple plugins that started from the same
+    /// state to contribute to one frame. Shapes, widgets, AccessKit nodes and outputs are
+    /// appended; area state is merged as described by
+    /// [`CreateContextSnapshot::apply_from_plugin`]; focus, interaction and popup state are taken
+    /// if the plugin changed them. Panics if this snapshot is not the `Created` variant.
+    pub fn merge(
+        self,
+        context: &Context,
+        base: &ContextBase,
+        owns_layer: impl Fn(LayerId) -> bool,
+    ) {
+        let Self::Created(value) = self else {
+            panic!("Snapshot was not `Created` variant.")
+        };
+        let owned = |layer: &LayerId| owns_layer(*layer) || !base.areas.contains(layer);
+        merge_label_selection(context, value.memory.label_selection_state.clone(), &owned);
+        if value.drag_payload.as_ref() != base.drag_payload.as_deref() {
+            drag::set_drag_payload(context, value.drag_payload);
+        }
+
+        let exposed = private_hack::Context::from_context(context);
+        let mut ctx = exposed.0.write();
+        let ctx = &mut *ctx;
+
+        if let Some(style) = value.style {
+            let style = style.resolve(ctx.memory.options.style());
+            match ctx.memory.options.theme() {
+                private_hack::Theme::Dark => ctx.memory.options.dark_style = style,
+                private_hack::Theme::Light => ctx.memory.options.light_style = style,
+            }
+        }
+
+        Self::merge_memory(&mut ctx.memory, value.memory, base, &owned);
+
+        for (id, snapshot) in value.viewports {
+            let (Some(viewport), Some(viewport_base)) =
+                (ctx.viewports.get_mut(&id), base.viewports.get(&id))
+            else {
+                continue;
+            };
+
+            Self::merge_viewport(viewport, snapshot, viewport_base, &owned);
+        }
+    }
+
+    /// Merges the plugin's memory into the host memory.
+    fn merge_memory(
+        memory: &mut private_hack::Memory,
+        snapshot: MemorySnapshot,
+        base: &ContextBase,
+        owned: &impl Fn(&LayerId) -> bool,
+    ) {
+        if snapshot.new_font_definitions.is_some() {
+            memory.new_font_definitions = snapshot.new_font_definitions;
+        }
+        memory
+            .add_fonts
+            .extend(snapshot.add_fonts.into_iter().skip(base.add_fonts));
+
+        memory.to_global.extend(
+            snapshot
+                .to_global
+                .into_iter()
+                .filter(|(layer, _)| owned(layer)),
+        );
+
+        merge_areas(&mut memory.areas, snapshot.areas, owned);
+
+        for (id, viewport_base) in &base.viewports {
+            if let Some(focus) = snapshot.focus.get(id) {
+                let focused = focus.focused_widget.as_ref().map(|x| x.id);
+                if focused != viewport_base.focused {
+                    memory.focus.insert(*id, focus.clone());
+                }
+            }
+
+            if let Some(interaction) = snapshot.interactions.get(id) {
+                let ids = (
+                    interaction.potential_click_id,
+                    interaction.potential_drag_id,
+                );
+                if ids != viewport_base.interaction {
+                    memory.interactions.insert(*id, interaction.clone());
+                }
+            }
+
+            let popup = snapshot.popups.get(id);
+            if popup.map(|x| x.id) != viewport_base.popup {
+                match popup {
+                    Some(popup) => memory.popups.insert(*id, *popup),
+                    None => memory.popups.remove(id),
+                };
+            }
+        }
+    }
+
+    /// Merges the content of a plugin's viewport into the host viewport.
+    fn merge_viewport(
+        viewport: &mut private_hack::ViewportState,
+        snapshot: ViewportStateSnapshot,
+        base: &ViewportBase,
+        owned: &impl Fn(&LayerId) -> bool,
+    ) {
+        let graphics = snapshot.graphics.as_inner().iter().zip(ORDERS);
+        for (paint_lists, order) in graphics {
+            for (id, paint_list) in paint_lists {
+                let layer = LayerId::new(order, *id);
+                let skip = base.shapes.get(&layer).copied().unwrap_or_default();
+                if skip < paint_list.as_inner().len() {
+                    viewport.graphics.as_inner_mut()[order as usize]
+                        .entry(*id)
+                        .or_default()
+                        .as_inner_mut()
+                        .extend(paint_list.as_inner()[skip..].iter().cloned());
+                }
+            }
+        }
+
+        let this_pass = snapshot.this_pass;
+        let widgets = this_pass.widgets;
+        let host_widgets = &mut viewport.this_pass.widgets;
+        for (layer, rects) in widgets.by_layer {
+            let layer_widgets = host_widgets.by_layer.entry(layer).or_default();
+            for rect in rects {
+                if !base.widgets.contains(&rect.id) && !host_widgets.by_id.contains_key(&rect.id) {
+                    host_widgets
+                        .by_id
+                        .insert(rect.id, (layer_widgets.len(), rect.clone()));
+                    layer_widgets.push(rect);
+                }
+            }
+        }
+        for (id, info) in widgets.infos {
+            if !base.widgets.contains(&id) {
+                host_widgets.infos.entry(id).or_insert(info);
+            }
+        }
+
+        #[cfg(feature = "accesskit")]
+        if let (Some(host), Some(plugin)) = (
+            viewport.this_pass.accesskit_state.as_mut(),
+            this_pass.accesskit_state,
+        ) {
+            let added = |id: &Id| !base.accesskit_nodes.contains(id);
+            for (id, node) in plugin.nodes.into_iter().filter(|(id, _)| added(id)) {
+                host.nodes.entry(id).or_insert(node);
+            }
+            for (id, parent) in plugin.parent_map.into_iter().filter(|(id, _)| added(id)) {
+                host.parent_map.entry(id).or_insert(parent);
+            }
+        }
+
+        viewport.this_pass.used_ids.extend(this_pass.used_ids);
+        viewport.this_pass.layers.extend(
+            this_pass
+                .layers
+                .into_iter()
+                .filter(|(layer, _)| owned(layer)),
+        );
+        viewport
+            .this_pass
+            .highlight_next_pass
+            .extend(this_pass.highlight_next_pass);
+
+        let output = snapshot.output;
+        viewport
+            .output
+            .commands
+            .extend(output.commands.into_iter().skip(base.output_commands));
+        viewport
+            .output
+            .events
+            .extend(output.events.into_iter().skip(base.output_events));
+        if output.cursor_icon != base.cursor_icon {
+            viewport.output.cursor_icon = output.cursor_icon;
+        }
+        if output.ime != base.ime {
+            viewport.output.ime = output.ime;
+        }
+        viewport.output.mutable_text_under_cursor |= output.mutable_text_under_cursor;
+
+        viewport
+            .commands
+            .extend(snapshot.commands.into_iter().skip(base.commands));
+    }
+}
+
+/// Merges a plugin's area state into the host's area state. Layers which the plugin owns, or
+/// which the host did not know about, take their position, visibility, sublayers and requests
+/// to move to the top from the plugin; new layers are appended to the end of the host's
+/// order. All other layers keep the host's state, and the order computed at the end of the
+/// last pass is never changed, so bring-to-front requests from the host and from every plugin
+/// are resolved together when the host's pass ends.
+pub(crate) fn merge_areas(
+    host: &mut ViewportIdMap<private_hack::Areas>,
+    plugin: ViewportIdMap<private_hack::Areas>,
+    owns_layer: &dyn Fn(&LayerId) -> bool,
+) {
+    for (id, areas) in plugin {
+        let host_areas = host.entry(id).or_default();
+        let owned: HashSet<_> = areas
+            .order
+            .iter()
+            .filter(|x| owns_layer(x) || !host_areas.order.contains(x))
+            .copied()
+            .collect();
+
+        for layer in areas.order.iter().filter(|x| owned.contains(x)) {
+            if let Some(state) = areas.areas.get(&layer.id) {
+                host_areas.areas.insert(layer.id, *state);
+            }
+            if !host_areas.order.contains(layer) {
+                host_areas.order.push(*layer);
+            }
+            if areas.visible_areas_current_frame.contains(layer) {
+                host_areas.visible_areas_current_frame.insert(*layer);
+            }
+            if areas.wants_to_be_on_top.contains(layer) {
+                host_areas.wants_to_be_on_top.insert(*layer);
+            }
+        }
+
+        for (parent, children) in areas.sublayers {
+            let parent_owned = owned.contains(&parent);
+            let children = children
+                .into_iter()
+                .filter(|x| parent_owned || owned.contains(x))
+                .collect::<Vec<_>>();
+            if !children.is_empty() {
+                host_areas
+                    .sublayers
+                    .entry(parent)
+                    .or_default()
+                    .extend(children);
+            }
+        }
+    }
+}
+
+/// Merges a plugin's text selection into the host's. Plugins only receive text selected within
+/// their own layers, so the plugin's selection is taken if it lies within one of its layers, or
+/// if the host's selection was visible to the plugin. Otherwise, the host's selection is kept.
+pub(crate) fn merge_label_selection(
+    context: &Context,
+    plugin: private_hack::LabelSelectionState,
+    owns_layer: &dyn Fn(&LayerId) -> bool,
+) {
+    context.with_plugin(|state: &mut text_selection::LabelSelectionState| {
+        let host = private_hack::LabelSelectionState::from_label_selection_state(state);
+        let plugin_selected = plugin
+            .selection
+            .as_ref()
+            .is_some_and(|x| owns_layer(&x.layer_id));
+        let host_visible = host
+            .selection
+            .as_ref()
+            .is_none_or(|x| owns_layer(&x.layer_id));
+        if plugin_selected || host_visible {
+            *state = plugin.into();
+        }
+    });
+}
+
+/// Restores the widgets, widget information and AccessKit nodes of the layers which were withheld
+/// from a plugin that only received the visible layers, so that applying the plugin's pass state
+/// does not remove them from the host. Widgets that the plugin added to those layers are placed
+/// after the host's.
+pub(crate) fn restore_hidden_pass_state(
+    plugin: &mut private_hack::PassState,
+    host: &private_hack::PassState,
+    visible_layers: &HashSet<LayerId>,
+) {
+    let hidden = |id: &Id| {
+        host.widgets
+            .by_id
+            .get(id)
+            .is_some_and(|(_, x)| !visible_layers.contains(&x.layer_id))
+    };
+
+    let hidden_layers = host
+        .widgets
+        .by_layer
+        .iter()
+        .filter(|(layer, _)| !visible_layers.contains(layer));
+    for (layer, rects) in hidden_layers {
+        let added = plugin.widgets.by_layer.remove(layer).unwrap_or_default();
+        let layer_widgets = plugin.widgets.by_layer.entry(*layer).or_default();
+        for rect in rects.iter().cloned().chain(added) {
+            plugin
+                .widgets
+                .by_id
+                .insert(rect.id, (layer_widgets.len(), rect.clone()));
+            layer_widgets.push(rect);
+        }
+    }
+
+    for (id, info) in host.widgets.infos.iter().filter(|(id, _)| hidden(id)) {
+        plugin
+            .widgets
+            .infos
+            .entry(*id)
+            .or_insert_with(|| info.clone());
+    }
+
+    #[cfg(feature = "accesskit")]
+    if let (Some(plugin), Some(host)) = (&mut plugin.accesskit_state, &host.accesskit_state) {
+        for (id, node) in host.nodes.iter().filter(|(id, _)| hidden(id)) {
+            plugin.nodes.entry(*id).or_insert_with(|| node.clone());
+        }
+    }
+}
+
+/// Merges the shapes of a plugin which only received the shapes of the visible layers into
+/// the host's shapes. The plugin's shapes replace those of the visible layers, and are added
+/// after the host's shapes on all other layers.
+pub(crate) fn merge_graphics(
+    host: &mut private_hack::GraphicLayers,
+    mut plugin: private_hack::GraphicLayers,
+    visible_layers: &HashSet<LayerId>,
+) {
+    let graphics = host.as_inner_mut().iter_mut().zip(plugin.as_inner_mut());
+    for ((host_lists, plugin_lists), order) in graphics.zip(ORDERS) {
+        for (id, mut paint_list) in std::mem::take(plugin_lists) {
+            let host_list = host_lists.entry(id).or_default();
+            if visible_layers.contains(&LayerId::new(order, id)) {
+                *host_list = paint_list;
+            } else {
+                host_list.as_inner_mut().append(paint_list.as_inner_mut());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a plugin that adds a button to its own area, starting from the host state, and
+    /// returns the plugin's snapshot along with the button's ID.
+    fn run_plugin(host: &Context, name: &str) -> (CreateContextSnapshot, Id) {
+        let plugin = Context::default();
+        CreateContextSnapshot::FromContext(host.clone(), ContextSnapshotDeltas::default())
+            .transfer()
+            .apply(&plugin);
+        let button = Area::new(Id::new(name))
+            .show(&plugin, |ui| ui.button(name).id)
+            .inner;
+        let snapshot =
+            CreateContextSnapshot::FromContext(plugin, ContextSnapshotDeltas::default()).transfer();
+        (snapshot, button)
+    }
+
+    #[test]
+    fn widgets_from_every_plugin_are_merged() {
+        let host = Context::default();
+        #[cfg(feature = "accesskit")]
+        host.enable_accesskit();
+        host.begin_pass(RawInput::default());
+        let base = ContextBase::capture(&host, None);
+
+        let (first, first_button) = run_plugin(&host, "first");
+        let (second, second_button) = run_plugin(&host, "second");
+        first.merge(&host, &base, |x| x.id == Id::new("first"));
+        second.merge(&host, &base, |x| x.id == Id::new("second"));
+
+        let exposed = private_hack::Context::from_context(&host);
+        let ctx = exposed.0.read();
+        let this_pass = &ctx.viewports[&ViewportId::ROOT].this_pass;
+        for id in [first_button, second_button] {
+            assert!(this_pass.widgets.by_id.contains_key(&id));
+            #[cfg(feature = "accesskit")]
+            {
+                let accesskit = this_pass
+                    .accesskit_state
+                    .as_ref()
+                    .expect("AccessKit was off.");
+                assert!(accesskit.nodes.contains_key(&id));
+                assert!(accesskit.parent_map.contains_key(&id));
+            }
+        }
+    }
+
+    /// Gets the IDs of the widget information and AccessKit nodes in a context.
+    fn pass_state_ids(context: &Context) -> (Vec<Id>, Vec<Id>) {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+        let this_pass = &ctx.viewports[&ViewportId::ROOT].this_pass;
+        let infos = this_pass.widgets.infos.keys().copied().collect();
+        #[cfg(feature = "accesskit")]
+        let nodes = this_pass
+            .accesskit_state
+            .iter()
+            .flat_map(|x| x.nodes.keys().copied())
+            .collect();
+        #[cfg(not(feature = "accesskit"))]
+        let nodes = Vec::new();
+        (infos, nodes)
+    }
+
+    #[test]
+    fn hidden_layers_keep_widget_information() {
+        let host = Context::default();
+        #[cfg(feature = "accesskit")]
+        host.enable_accesskit();
+        host.style_mut(|x| x.debug.show_interactive_widgets = true);
+        let _ = host.run(RawInput::default(), |_| {});
+        host.begin_pass(RawInput::default());
+        let mine = Area::new(Id::new("mine")).show(&host, |ui| ui.button("mine").id);
+        let other = Area::new(Id::new("other")).show(&host, |ui| ui.button("other").id);
+
+        let layers = HashSet::from([mine.response.layer_id]);
+        let filter = SnapshotFilter {
+            layers: Some(layers.clone()),
+            owned_layers: Some(layers.clone()),
+            ..Default::default()
+        };
+        let plugin = Context::default();
+        CreateContextSnapshot::Filtered(host.clone(), ContextSnapshotDeltas::default(), filter)
+            .transfer()
+            .apply(&plugin);
+        let (infos, nodes) = pass_state_ids(&plugin);
+        assert!(infos.contains(&mine.inner) && !infos.contains(&other.inner));
+        assert!(!nodes.contains(&other.inner));
+        #[cfg(feature = "accesskit")]
+        assert!(nodes.contains(&mine.inner));
+
+        CreateContextSnapshot::FromContext(plugin, ContextSnapshotDeltas::default())
+            .transfer()
+            .apply_from_plugin(&host, |x| layers.contains(&x), Some(&layers), false);
+        let (infos, nodes) = pass_state_ids(&host);
+        assert!(infos.contains(&mine.inner) && infos.contains(&other.inner));
+        assert_eq!(nodes.contains(&other.inner), cfg!(feature = "accesskit"));
+        let _ = host.end_pass();
+    }
+}
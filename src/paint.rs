@@ -0,0 +1,101 @@
+use crate::*;
+use egui::text::*;
+
+/// A shape which a plugin paints with [`Egui::paint`]. Plugins have no fonts outside of a
+/// transaction, so text is sent as a layout job and laid out by the host.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PaintShape {
+    /// A shape which is painted as-is. The galleys of text shapes are laid out again by
+    /// the host from their jobs.
+    Shape(#[serde(with = "snapshot::shape")] Shape),
+    /// Text which is laid out by the host and placed relative to a position.
+    Text {
+        /// The position of the anchor point.
+        pos: Pos2,
+        /// Which point of the text is placed at the position.
+        anchor: Align2,
+        /// Describes the text and its formatting.
+        job: LayoutJob,
+    },
+}
+
+impl PaintShape {
+    /// Creates text with a single font and color, placed so that the given anchor point of
+    /// the text is at the position.
+    pub fn text(
+        pos: Pos2,
+        anchor: Align2,
+        text: impl Into<String>,
+        font_id: FontId,
+        color: Color32,
+    ) -> Self {
+        Self::Text {
+            pos,
+            anchor,
+            job: LayoutJob::simple(text.into(), font_id, color, f32::INFINITY),
+        }
+    }
+
+    /// Converts this into a shape, laying out any text with the fonts of the given context.
+    fn into_shape(self, context: &Context) -> Shape {
+        match self {
+            Self::Shape(mut shape) => {
+                relayout(&mut shape, context);
+                shape
+            }
+            Self::Text { pos, anchor, job } => {
+                let galley = context.fonts_mut(|x| x.layout_job(job));
+                let rect = anchor.anchor_size(pos, galley.size());
+                Shape::galley(rect.min, galley, Color32::PLACEHOLDER)
+            }
+        }
+    }
+}
+
+impl From<Shape> for PaintShape {
+    fn from(value: Shape) -> Self {
+        Self::Shape(value)
+    }
+}
+
+/// Replaces the galleys of any text within the shape with ones laid out by the given context.
+fn relayout(shape: &mut Shape, context: &Context) {
+    match shape {
+        Shape::Vec(x) => {
+            for shape in x {
+                relayout(shape, context);
+            }
+        }
+        Shape::Text(x) => {
+            x.galley = context.fonts_mut(|f| f.layout_job((*x.galley.job).clone()));
+        }
+        _ => {}
+    }
+}
+
+/// The shapes which a plugin painted onto a layer without a transaction.
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayerPaint {
+    /// The layer onto which the shapes are painted.
+    pub layer: LayerId,
+    /// The shapes to paint.
+    pub shapes: Vec<PaintShape>,
+}
+
+impl LayerPaint {
+    /// Creates a new set of shapes to paint onto the given layer.
+    pub(crate) fn new(layer: LayerId, shapes: Vec<PaintShape>) -> Self {
+        Self { layer, shapes }
+    }
+
+    /// Paints the shapes onto the layer in the given context, clipped to the screen.
+    pub fn paint(self, context: &Context) {
+        let shapes = self
+            .shapes
+            .into_iter()
+            .map(|x| x.into_shape(context))
+            .collect::<Vec<_>>();
+        context.layer_painter(self.layer).extend(shapes);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::*;
+
+/// The `egui` state of a plugin that should be saved between application sessions.
+/// The host stores one of these for each plugin that has set a persistence key.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PersistedPluginState {
+    /// The states of the windows and other areas that the plugin displayed, along with
+    /// the viewports in which they were shown.
+    pub areas: Vec<(ViewportId, LayerId, AreaState)>,
+    /// The persisted entries of the plugin's `Memory::data`, encoded as RON.
+    /// This is only reported by plugins which enable the `persistence` feature.
+    pub memory: Option<String>,
+}
+
+impl PersistedPluginState {
+    /// Captures the states of the given layers from the context.
+    #[doc(hidden)]
+    pub fn capture_areas(
+        context: &Context,
+        layers: impl IntoIterator<Item = LayerId>,
+    ) -> Vec<(ViewportId, LayerId, AreaState)> {
+        let exposed = private_hack::Context::from_context(context);
+        let ctx = exposed.0.read();
+
+        layers
+            .into_iter()
+            .filter_map(|layer| {
+                ctx.memory.areas.iter().find_map(|(viewport, areas)| {
+                    areas
+                        .areas
+                        .get(&layer.id)
+                        .map(|state| (*viewport, layer, *state))
+                })
+            })
+            .collect()
+    }
+
+    /// Restores the states of this plugin's areas into the viewports of the context in
+    /// which they were captured.
+    #[doc(hidden)]
+    pub fn restore_areas(&self, context: &Context) {
+        let exposed = private_hack::Context::from_context(context);
+        let mut ctx = exposed.0.write();
+
+        for (viewport, layer, state) in &self.areas {
+            let areas = ctx.memory.areas.entry(*viewport).or_default();
+            areas.areas.insert(layer.id, *state);
+            if !areas.order.contains(layer) {
+                areas.order.push(*layer);
+            }
+        }
+    }
+}
+
+/// Encodes the persisted entries of the context's `Memory::data` as RON.
+#[cfg(feature = "persistence")]
+pub(crate) fn save_memory(context: &Context) -> Option<String> {
+    context.data(|data| ron::to_string(data).ok())
+}
+
+/// Decodes memory that was saved with [`save_memory`] and loads it into the context.
+/// Entries that synchronize the context with the host are retained.
+#[cfg(feature = "persistence")]
+pub(crate) fn load_memory(context: &Context, memory: &str) {
+    let Ok(data) = ron::from_str::<egui::util::IdTypeMap>(memory) else {
+        return;
+    };
+
+    context.data_mut(|x| {
+        let deltas = x.get_temp::<ContextSnapshotDeltas>(Id::NULL);
+        let last_style = x.get_temp::<LastStyle>(Id::NULL);
+        *x = data;
+
+        if let Some(deltas) = deltas {
+            x.insert_temp(Id::NULL, deltas);
+        }
+
+        if let Some(last_style) = last_style {
+            x.insert_temp(Id::NULL, last_style);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn areas_round_trip() {
+        let context = Context::default();
+        let _ = context.run(RawInput::default(), |ctx| {
+            Area::new(Id::new("saved"))
+                .fixed_pos(pos2(40.0, 30.0))
+                .show(ctx, |ui| ui.label("saved"));
+        });
+        let layer = LayerId::new(Order::Middle, Id::new("saved"));
+
+        let state = PersistedPluginState {
+            areas: PersistedPluginState::capture_areas(&context, [layer]),
+            memory: None,
+        };
+        let saved = ron::to_string(&state).expect("Failed to save state.");
+        let loaded = ron::from_str::<PersistedPluginState>(&saved).expect("Failed to load state.");
+
+        let restored = Context::default();
+        loaded.restore_areas(&restored);
+        let position = restored.memory(|x| x.area_rect(Id::new("saved")).map(|x| x.min));
+        assert_eq!(position, Some(pos2(40.0, 30.0)));
+        assert!(restored.memory(|x| x.layer_ids().any(|x| x == layer)));
+    }
+
+    #[test]
+    fn areas_return_to_their_viewports() {
+        let context = Context::default();
+        let _ = context.run(RawInput::default(), |ctx| {
+            Area::new(Id::new("child")).show(ctx, |ui| ui.label("child"));
+        });
+        let layer = LayerId::new(Order::Middle, Id::new("child"));
+        let child = ViewportId::from_hash_of("child");
+        {
+            let exposed = private_hack::Context::from_context(&context);
+            let mut ctx = exposed.0.write();
+            let root = ctx.memory.areas.entry(ViewportId::ROOT).or_default();
+            let state = root.areas.remove(&layer.id).expect("Area was not shown.");
+            ctx.memory
+                .areas
+                .entry(child)
+                .or_default()
+                .areas
+                .insert(layer.id, state);
+        }
+
+        let state = PersistedPluginState {
+            areas: PersistedPluginState::capture_areas(&context, [layer]),
+            memory: None,
+        };
+        let restored = Context::default();
+        state.restore_areas(&restored);
+
+        let exposed = private_hack::Context::from_context(&restored);
+        let ctx = exposed.0.read();
+        assert!(ctx.memory.areas[&child].areas.contains_key(&layer.id));
+        assert!(ctx
+            .memory
+            .areas
+            .get(&ViewportId::ROOT)
+            .is_none_or(|x| !x.areas.contains_key(&layer.id)));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn memory_round_trip_keeps_synchronization_state() {
+        let context = Context::default();
+        context.data_mut(|x| x.insert_persisted(Id::new("count"), 5u32));
+        let memory = save_memory(&context).expect("Failed to save memory.");
+
+        let restored = Context::default();
+        let deltas = ContextSnapshotDeltas::default();
+        restored.data_mut(|x| x.insert_temp(Id::NULL, deltas));
+        load_memory(&restored, &memory);
+
+        let count = restored.data_mut(|x| x.get_persisted::<u32>(Id::new("count")));
+        assert_eq!(count, Some(5));
+        assert!(restored
+            .data(|x| x.get_temp::<ContextSnapshotDeltas>(Id::NULL))
+            .is_some());
+    }
+}
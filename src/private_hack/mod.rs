@@ -25,7 +25,7 @@ mod animation_manager;
 mod hit_test;
 mod interaction;
 mod layers;
-mod widget_rect;
+pub mod widget_rect;
 
 pub struct Context(pub Arc<egui::mutex::RwLock<ContextImpl>>);
 
@@ -245,6 +245,38 @@ impl NumberFormatter {
     }
 }
 
+impl From<egui::style::NumberFormatter> for NumberFormatter {
+    fn from(value: egui::style::NumberFormatter) -> Self {
+        unsafe { transmute(value) }
+    }
+}
+
+impl From<NumberFormatter> for egui::style::NumberFormatter {
+    fn from(value: NumberFormatter) -> Self {
+        unsafe { transmute(value) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct DebugOptions {
+    pub debug_on_hover: bool,
+    pub debug_on_hover_with_all_modifiers: bool,
+    pub hover_shows_next: bool,
+    pub show_expand_width: bool,
+    pub show_expand_height: bool,
+    pub show_resize: bool,
+    pub show_interactive_widgets: bool,
+    pub show_widget_hits: bool,
+    pub show_unaligned: bool,
+}
+
+#[cfg(debug_assertions)]
+impl From<egui::style::DebugOptions> for DebugOptions {
+    fn from(value: egui::style::DebugOptions) -> Self {
+        unsafe { transmute(value) }
+    }
+}
+
 impl Default for Style {
     fn default() -> Self {
         #[allow(deprecated)]
@@ -262,7 +294,7 @@ impl Default for Style {
             visuals: Visuals::default(),
             animation_time: 1.0 / 12.0,
             #[cfg(debug_assertions)]
-            debug: Default::default(),
+            debug: egui::style::DebugOptions::default().into(),
             explanation_tooltips: false,
             url_in_tooltip: false,
             always_scroll_the_only_direction: false,
@@ -293,7 +325,7 @@ pub struct InputOptions {
     pub surrender_focus_on: SurrenderFocusOn,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct Style {
     pub override_text_style: Option<TextStyle>,
     pub override_font_id: Option<FontId>,
@@ -343,7 +375,7 @@ pub struct InteractionState {
 
 #[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct Focus {
-    focused_widget: Option<FocusWidget>,
+    pub focused_widget: Option<FocusWidget>,
     id_previous_frame: Option<Id>,
     id_next_frame: Option<Id>,
     #[cfg(feature = "accesskit")]
@@ -357,7 +389,7 @@ pub struct Focus {
 }
 
 #[derive(Clone, serde::Deserialize, serde::Serialize)]
-struct FocusWidget {
+pub struct FocusWidget {
     pub id: Id,
     pub filter: EventFilter,
 }
@@ -389,15 +421,33 @@ pub struct NamedContextCallback {
 }
 
 pub struct Plugins {
-    plugins: ahash::HashMap<std::any::TypeId, Arc<Mutex<PluginHandle>>>,
-    plugins_ordered: PluginsOrdered,
+    plugins: ahash::HashMap<std::any::TypeId, Arc<egui::mutex::Mutex<PluginHandle>>>,
+    pub plugins_ordered: PluginsOrdered,
 }
 
 #[derive(Clone, Default)]
-pub struct PluginsOrdered(Vec<Arc<Mutex<PluginHandle>>>);
+pub struct PluginsOrdered(pub Vec<Arc<egui::mutex::Mutex<PluginHandle>>>);
 
 pub struct PluginHandle {
-    plugin: Box<dyn Plugin>,
+    pub plugin: Box<dyn Plugin>,
+}
+
+/// Holds the callbacks added with `Context::on_begin_pass` and `Context::on_end_pass`.
+pub struct CallbackPlugin {
+    pub on_begin_plugins: Vec<(&'static str, ContextCallback)>,
+    pub on_end_plugins: Vec<(&'static str, ContextCallback)>,
+}
+
+impl CallbackPlugin {
+    /// The name that `egui` gives this plugin.
+    const DEBUG_NAME: &'static str = "CallbackPlugins";
+
+    /// Converts the `egui` plugin reference to a reference of this type, or returns `None`
+    /// if the plugin is of another type.
+    pub fn from_plugin(plugin: &dyn Plugin) -> Option<&Self> {
+        (plugin.debug_name() == Self::DEBUG_NAME)
+            .then(|| unsafe { &*(plugin as *const dyn Plugin as *const Self) })
+    }
 }
 
 pub struct WrappedTextureManager(Arc<RwLock<epaint::TextureManager>>);
@@ -438,6 +488,13 @@ pub struct DebugRect {
     pub is_clicking: bool,
 }
 
+#[cfg(feature = "accesskit")]
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct AccessKitPassState {
+    pub nodes: IdMap<accesskit::Node>,
+    pub parent_map: IdMap<Id>,
+}
+
 #[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct PassState {
     pub used_ids: IdMap<Rect>,
@@ -450,7 +507,6 @@ pub struct PassState {
     pub scroll_target: [Option<ScrollTarget>; 2],
     pub scroll_delta: (Vec2, style::ScrollAnimation),
     #[cfg(feature = "accesskit")]
-    #[serde(skip)]
     pub accesskit_state: Option<AccessKitPassState>,
     pub highlight_next_pass: IdSet,
     #[cfg(debug_assertions)]
@@ -558,7 +614,7 @@ pub struct PerLayerState {
 }
 
 #[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
-pub struct Sense(u8);
+pub struct Sense(pub u8);
 
 
 #[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
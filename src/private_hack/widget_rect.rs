@@ -12,7 +12,7 @@ pub struct WidgetRect {
 
 #[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct WidgetRects {
-    by_layer: ahash::HashMap<LayerId, Vec<WidgetRect>>,
-    by_id: IdMap<(usize, WidgetRect)>,
-    infos: IdMap<WidgetInfo>,
+    pub by_layer: ahash::HashMap<LayerId, Vec<WidgetRect>>,
+    pub by_id: IdMap<(usize, WidgetRect)>,
+    pub infos: IdMap<WidgetInfo>,
 }
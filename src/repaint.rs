@@ -0,0 +1,30 @@
+use crate::*;
+use std::time::*;
+
+/// Clears any repaint requests from the context, so that requests made during
+/// the current transaction can be identified.
+pub(crate) fn reset(context: &Context) {
+    let exposed = private_hack::Context::from_context(context);
+    let mut ctx = exposed.0.write();
+    for viewport in ctx.viewports.values_mut() {
+        viewport.repaint.outstanding = 0;
+        viewport.repaint.repaint_delay = Duration::MAX;
+    }
+}
+
+/// Gets the repaint delay that was requested for each viewport since the last call to [`reset`].
+pub(crate) fn requests(context: &Context) -> ViewportIdMap<Duration> {
+    let exposed = private_hack::Context::from_context(context);
+    let ctx = exposed.0.read();
+    ctx.viewports
+        .iter()
+        .filter_map(|(id, viewport)| {
+            if 0 < viewport.repaint.outstanding {
+                Some((*id, Duration::ZERO))
+            } else {
+                (viewport.repaint.repaint_delay < Duration::MAX)
+                    .then_some((*id, viewport.repaint.repaint_delay))
+            }
+        })
+        .collect()
+}
@@ -3,11 +3,12 @@ use egui::emath::TSTransform;
 use egui::epaint::text::FontDefinitions;
 use serde::ser::{SerializeMap, SerializeSeq, SerializeTuple};
 
-use egui::{AreaState, PlatformOutput, ViewportCommand};
+use egui::{AreaState, PlatformOutput, RawInput, ViewportCommand};
+use std::borrow::Cow;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-use crate::{private_hack::*, LastStyle};
+use crate::{private_hack::*, InputRedaction, LastStyle};
 
 /// Tracks changes that occur to a [`Context`](super::Context) so that a
 /// partial `ContextSnapshot` can be generated containing only information
@@ -22,6 +23,9 @@ pub struct ContextSnapshotDeltas {
     pub(super) font_definitions_count: u64,
     /// The number of frames that have elapsed.
     pub(super) frame_count: u64,
+    /// The pass whose complete, unredacted input the context holds, from which the input of
+    /// the following pass may be rebuilt. This is `u64::MAX` if the input may not be rebuilt.
+    pub(super) input_pass_count: u64,
     /// The number of passes that have elapsed.
     pub(super) pass_count: u64,
     /// The number of times that the style has changed.
@@ -37,6 +41,7 @@ impl ContextSnapshotDeltas {
         let mut previous_deltas = ctx.memory.data.get_temp::<Self>(Id::NULL).unwrap_or(Self {
             font_definitions_count: 0,
             frame_count: 0,
+            input_pass_count: u64::MAX,
             pass_count: 0,
             style_count: 0,
         });
@@ -70,6 +75,26 @@ impl ContextSnapshotDeltas {
         ctx.memory.data.insert_temp(Id::NULL, previous_deltas);
         previous_deltas
     }
+
+    /// The number of frames that had elapsed when these deltas were taken.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Whether the font definitions and style were unchanged between these deltas and the other deltas.
+    pub fn same_resources(&self, other: &Self) -> bool {
+        self.font_definitions_count == other.font_definitions_count
+            && self.style_count == other.style_count
+    }
+
+    /// Whether these deltas will cause a full synchronization, because the
+    /// other side of the transaction has no prior state to build upon.
+    pub fn is_full_resync(&self) -> bool {
+        self.font_definitions_count == u64::MAX
+            || self.frame_count == u64::MAX
+            || self.pass_count == u64::MAX
+            || self.style_count == u64::MAX
+    }
 }
 
 impl Default for ContextSnapshotDeltas {
@@ -77,6 +102,7 @@ impl Default for ContextSnapshotDeltas {
         Self {
             font_definitions_count: u64::MAX,
             frame_count: u64::MAX,
+            input_pass_count: u64::MAX,
             pass_count: u64::MAX,
             style_count: u64::MAX,
         }
@@ -88,8 +114,12 @@ impl Default for ContextSnapshotDeltas {
 pub struct ContextShapshotBorrow<'a> {
     /// The deltas describing the current context state.
     pub(super) deltas: &'a ContextSnapshotDeltas,
+    /// The shared drag-and-drop payload, if any.
+    pub(super) drag_payload: Option<Arc<crate::DragPayload>>,
     /// The `ContextImpl::font_definitions` field.
     pub(super) font_definitions: Option<&'a FontDefinitions>,
+    /// The state of the context's `LabelSelectionState` plugin.
+    pub(super) label_selection_state: &'a LabelSelectionState,
     /// The `ContextImpl::memory` field.
     pub(super) memory: &'a Memory,
     /// The context's style.
@@ -100,6 +130,19 @@ pub struct ContextShapshotBorrow<'a> {
     pub(super) last_viewport: &'a ViewportId,
     /// The `ContextImpl::viewports` field.
     pub(super) viewports: &'a ViewportIdMap<ViewportState>,
+    /// The `ContextImpl::is_accesskit_enabled` field.
+    pub(super) accesskit_enabled: bool,
+    /// The input which should be withheld from the recipient, if any.
+    pub(super) redaction: Option<&'a InputRedaction>,
+    /// The layers whose shapes, widgets and AccessKit nodes are sent, or `None`
+    /// if those of every layer are sent.
+    pub(super) layers: Option<&'a std::collections::HashSet<LayerId>>,
+    /// The layers which the recipient owns, if it is a plugin. Text selected in other layers
+    /// and the contents of the clipboard are withheld from plugins.
+    pub(super) owned_layers: Option<&'a std::collections::HashSet<LayerId>>,
+    /// The pass whose input the recipient holds, if the recipient should rebuild its
+    /// input from raw input where possible.
+    pub(super) replay_pass: Option<u64>,
 }
 
 /// Holds the instantaneous state of a `Context`. May be used to synchronize
@@ -108,6 +151,8 @@ pub struct ContextShapshotBorrow<'a> {
 pub struct ContextSnapshot {
     /// The deltas describing the current context state.
     pub(super) deltas: ContextSnapshotDeltas,
+    /// The shared drag-and-drop payload, if any.
+    pub(super) drag_payload: Option<crate::DragPayload>,
     /// The `ContextImpl::font_definitions` field.
     pub(super) font_definitions: Option<FontDefinitions>,
     /// The `ContextImpl::memory` field.
@@ -115,25 +160,28 @@ pub struct ContextSnapshot {
     /// The `Memory::options` field.
     pub(super) options: OptionsSnapshot,
     /// The context's style.
-    pub(super) style: Option<Arc<Style>>,
+    pub(super) style: Option<StyleSnapshot>,
     /// The `ContextImpl::new_zoom_factor` field.
     pub(super) new_zoom_factor: Option<f32>,
     /// The `ContextImpl::last_viewport` field.
     pub(super) last_viewport: ViewportId,
     /// The `ContextImpl::viewports` field.
     pub(super) viewports: ViewportIdMap<ViewportStateSnapshot>,
+    /// The `ContextImpl::is_accesskit_enabled` field.
+    #[cfg_attr(not(feature = "accesskit"), allow(dead_code))]
+    pub(super) accesskit_enabled: bool,
 }
 
 impl ContextSnapshot {
     /// The number of fields that this struct has.
-    const FIELDS: usize = 8;
+    const FIELDS: usize = 10;
 }
 
 /// Holds the instantaneous state of a `Memory` for synchronizing
 /// between two separate contexts.
 #[derive(Clone)]
 pub(super) struct MemorySnapshot {
-    /// The `LabelSelectionState` object stored in `Memory::data`
+    /// The state of the context's `LabelSelectionState` plugin.
     pub label_selection_state: LabelSelectionState,
     /// The `Memory::new_font_definitions` field.
     pub new_font_definitions: Option<epaint::text::FontDefinitions>,
@@ -151,7 +199,7 @@ pub(super) struct MemorySnapshot {
     pub interactions: ViewportIdMap<InteractionState>,
     /// The `Memory::focus` field.
     pub focus: ViewportIdMap<Focus>,
-/// The `Memory::popups` field.
+    /// The `Memory::popups` field.
     pub popups: ViewportIdMap<OpenPopup>,
 }
 
@@ -193,6 +241,39 @@ impl OptionsSnapshot {
     const FIELDS: usize = 10;
 }
 
+/// Holds a style for synchronizing between two separate contexts.
+#[derive(Clone, serde::Deserialize)]
+pub(super) struct StyleSnapshot {
+    /// The style, without its debug options or number formatter.
+    style: Arc<Style>,
+    /// The `Style::debug` field, if the sender was built with debug assertions.
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    debug: Option<DebugOptions>,
+    /// The name under which the `Style::number_formatter` was registered, if any.
+    number_formatter: Option<String>,
+}
+
+impl StyleSnapshot {
+    /// The number of fields that this struct has.
+    const FIELDS: usize = 3;
+
+    /// Gets the style to apply to a context whose style is currently `current`. The debug
+    /// options and number formatter are kept from the current style if they were not sent.
+    pub fn resolve(self, current: &Style) -> Arc<Style> {
+        let mut style = self.style;
+        let inner = Arc::make_mut(&mut style);
+        inner.number_formatter = self.number_formatter.map_or_else(
+            || current.number_formatter.clone(),
+            crate::named_number_formatter,
+        );
+        #[cfg(debug_assertions)]
+        {
+            inner.debug = self.debug.unwrap_or(current.debug);
+        }
+        style
+    }
+}
+
 /// A serialized version of `epaint::text::TextWrapping`
 /// which ensures that `max_rows` never overflows
 /// when serializing across 32-bit or 64-bit architectures.
@@ -239,7 +320,7 @@ pub(super) struct ViewportStateSnapshot {
     /// The `ViewportState::builder` field.
     pub builder: ViewportBuilder,
     /// The `ViewportState::input` field.
-    pub input: InputState,
+    pub input: ViewportInputSnapshot,
     /// The `ViewportState::this_pass` field.
     pub this_pass: PassState,
     /// The `ViewportState::prev_pass` field.
@@ -265,17 +346,93 @@ impl ViewportStateSnapshot {
     const FIELDS: usize = 12 - 1;
 }
 
+/// Holds the input of a viewport for synchronizing between two separate contexts.
+#[derive(Clone, serde::Deserialize)]
+pub(super) enum ViewportInputSnapshot {
+    /// The processed input state.
+    Full {
+        /// The input state of the viewport.
+        input: Box<InputState>,
+        /// Whether input was withheld, in which case the input state may not be used to
+        /// rebuild the input of later passes.
+        redacted: bool,
+    },
+    /// The raw input of the pass, from which the recipient rebuilds the input state that it
+    /// holds for the previous pass.
+    Raw {
+        /// The raw input that the sender received at the beginning of the pass.
+        raw: Box<RawInput>,
+        /// Whether an immediate repaint was requested during the previous pass.
+        requested_immediate_repaint_prev_pass: bool,
+        /// The number of physical pixels for each point.
+        pixels_per_point: f32,
+        /// The options with which the input is processed.
+        options: egui::InputOptions,
+    },
+    /// The recipient already holds the input state of this pass.
+    Unchanged,
+}
+
+impl Default for ViewportInputSnapshot {
+    fn default() -> Self {
+        Self::Full {
+            input: Box::default(),
+            redacted: false,
+        }
+    }
+}
+
+/// A borrowed version of a [`ViewportInputSnapshot`].
+#[derive(serde::Serialize)]
+enum ViewportInputBorrow<'a> {
+    /// The processed input state.
+    Full {
+        /// The input state of the viewport.
+        input: &'a InputState,
+        /// Whether input was withheld.
+        redacted: bool,
+    },
+    /// The raw input of the pass.
+    Raw {
+        /// The raw input that the sender received at the beginning of the pass.
+        raw: &'a RawInput,
+        /// Whether an immediate repaint was requested during the previous pass.
+        requested_immediate_repaint_prev_pass: bool,
+        /// The number of physical pixels for each point.
+        pixels_per_point: f32,
+        /// The options with which the input is processed.
+        options: &'a InputOptions,
+    },
+    /// The recipient already holds the input state of this pass.
+    Unchanged,
+}
+
 impl<'a> serde::Serialize for ContextShapshotBorrow<'a> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         let mut seq = serializer.serialize_tuple(ContextSnapshot::FIELDS)?;
         seq.serialize_element(self.deltas)?;
         seq.serialize_element(&self.font_definitions)?;
-        seq.serialize_element(&SnapshotSerialize(self.memory))?;
+        seq.serialize_element(&MemorySerialize {
+            label_selection_state: self.label_selection_state,
+            memory: self.memory,
+            owned_layers: self.owned_layers,
+        })?;
         seq.serialize_element(&SnapshotSerialize(&self.memory.options))?;
-        seq.serialize_element(&self.style)?;
+        seq.serialize_element(&self.style.as_deref().map(StyleSerialize))?;
         seq.serialize_element(&self.new_zoom_factor)?;
         seq.serialize_element(&self.last_viewport)?;
-        seq.serialize_element(&SnapshotSerialize(self.viewports))?;
+        seq.serialize_element(&ViewportsSerialize {
+            input_options: &self.memory.options.input_options,
+            last_viewport: *self.last_viewport,
+            layers: self.layers,
+            owned_layers: self.owned_layers,
+            pass_count: self.deltas.pass_count,
+            redaction: self.redaction,
+            replay_pass: self.replay_pass,
+            viewports: self.viewports,
+        })?;
+        seq.serialize_element(&self.accesskit_enabled)?;
+        seq.serialize_element(&self.drag_payload)?;
         seq.end()
     }
 }
@@ -283,25 +440,62 @@ impl<'a> serde::Serialize for ContextShapshotBorrow<'a> {
 /// Implements custom, snapshot-specific serialization logic for type `T`.
 pub struct SnapshotSerialize<'a, T>(&'a T);
 
-impl<'a> serde::Serialize for SnapshotSerialize<'a, Memory> {
+/// Serializes a style along with the parts of it that `Style` does not serialize.
+struct StyleSerialize<'a>(&'a Style);
+
+impl<'a> serde::Serialize for StyleSerialize<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[cfg(debug_assertions)]
+        let debug = Some(self.0.debug);
+        #[cfg(not(debug_assertions))]
+        let debug = None::<DebugOptions>;
+
+        let mut serialize_tuple = serializer.serialize_tuple(StyleSnapshot::FIELDS)?;
+        serialize_tuple.serialize_element(self.0)?;
+        serialize_tuple.serialize_element(&debug)?;
+        serialize_tuple
+            .serialize_element(&crate::number_formatter_name(&self.0.number_formatter))?;
+        serialize_tuple.end()
+    }
+}
+
+/// Serializes the memory of a context, withholding text selected in layers that the
+/// recipient does not own.
+struct MemorySerialize<'a> {
+    /// The state of the context's `LabelSelectionState` plugin.
+    label_selection_state: &'a LabelSelectionState,
+    /// The memory to serialize.
+    memory: &'a Memory,
+    /// The layers which the recipient owns, if it is a plugin.
+    owned_layers: Option<&'a std::collections::HashSet<LayerId>>,
+}
+
+impl<'a> serde::Serialize for MemorySerialize<'a> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let withheld = self.owned_layers.is_some_and(|layers| {
+            self.label_selection_state
+                .selection
+                .as_ref()
+                .is_some_and(|x| !layers.contains(&x.layer_id))
+        });
+
         let mut serialize_tuple = serializer.serialize_tuple(MemorySnapshot::FIELDS)?;
-        serialize_tuple.serialize_element(LabelSelectionState::from_label_selection_state(
-            &self
-                .0
-                .data
-                .get_temp::<egui::text_selection::LabelSelectionState>(Id::new(ViewportId::ROOT))
-                .unwrap_or_default(),
-        ))?;
-        serialize_tuple.serialize_element(&self.0.new_font_definitions)?;
-        serialize_tuple.serialize_element(&self.0.add_fonts)?;
-        serialize_tuple.serialize_element(&self.0.viewport_id)?;
-        serialize_tuple.serialize_element(&self.0.everything_is_visible)?;
-        serialize_tuple.serialize_element(&self.0.to_global)?;
-        serialize_tuple.serialize_element(&SnapshotSerialize(&self.0.areas))?;
-        serialize_tuple.serialize_element(&self.0.interactions)?;
-        serialize_tuple.serialize_element(&self.0.focus)?;
-        serialize_tuple.serialize_element(&self.0.popups)?;
+        if withheld {
+            serialize_tuple.serialize_element(LabelSelectionState::from_label_selection_state(
+                &Default::default(),
+            ))?;
+        } else {
+            serialize_tuple.serialize_element(self.label_selection_state)?;
+        }
+        serialize_tuple.serialize_element(&self.memory.new_font_definitions)?;
+        serialize_tuple.serialize_element(&self.memory.add_fonts)?;
+        serialize_tuple.serialize_element(&self.memory.viewport_id)?;
+        serialize_tuple.serialize_element(&self.memory.everything_is_visible)?;
+        serialize_tuple.serialize_element(&self.memory.to_global)?;
+        serialize_tuple.serialize_element(&SnapshotSerialize(&self.memory.areas))?;
+        serialize_tuple.serialize_element(&self.memory.interactions)?;
+        serialize_tuple.serialize_element(&self.memory.focus)?;
+        serialize_tuple.serialize_element(&self.memory.popups)?;
         serialize_tuple.end()
     }
 }
@@ -370,35 +564,159 @@ impl<'a> serde::Serialize for SnapshotSerialize<'a, AreaState> {
     }
 }
 
-impl<'a> serde::Serialize for SnapshotSerialize<'a, ViewportState> {
-    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut serialize_tuple = serializer.serialize_tuple(ViewportStateSnapshot::FIELDS)?;
-        serialize_tuple.serialize_element(&self.0.class)?;
-        //serialize_tuple.serialize_element(&self.0.builder)?;
-        serialize_tuple.serialize_element(&self.0.input)?;
-        serialize_tuple.serialize_element(&self.0.this_pass)?;
-        serialize_tuple.serialize_element(&self.0.prev_pass)?;
-        serialize_tuple.serialize_element(&self.0.used)?;
-        serialize_tuple.serialize_element(&self.0.hits)?;
-        serialize_tuple.serialize_element(&self.0.interact_widgets)?;
-        serialize_tuple.serialize_element(&SnapshotSerialize(&self.0.graphics))?;
-        serialize_tuple.serialize_element(&self.0.output)?;
-        serialize_tuple.serialize_element(&self.0.commands)?;
-        serialize_tuple.serialize_element(&self.0.num_multipass_in_row)?;
-        serialize_tuple.end()
-    }
+/// Serializes the viewports of a context, withholding redacted input.
+struct ViewportsSerialize<'a> {
+    /// The options with which input is processed.
+    input_options: &'a InputOptions,
+    /// The viewport whose passes are counted by the context's deltas.
+    last_viewport: ViewportId,
+    /// The layers whose shapes, widgets and AccessKit nodes are sent, or `None`
+    /// if those of every layer are sent. Output events are withheld unless every layer is sent.
+    layers: Option<&'a std::collections::HashSet<LayerId>>,
+    /// The layers which the recipient owns, if it is a plugin.
+    owned_layers: Option<&'a std::collections::HashSet<LayerId>>,
+    /// The number of passes that have elapsed in the context.
+    pass_count: u64,
+    /// The input which should be withheld, if any.
+    redaction: Option<&'a InputRedaction>,
+    /// The pass whose input the recipient holds, if the recipient should rebuild its
+    /// input from raw input where possible.
+    replay_pass: Option<u64>,
+    /// The viewports to serialize.
+    viewports: &'a ViewportIdMap<ViewportState>,
 }
 
-impl<'a> serde::Serialize for SnapshotSerialize<'a, ViewportIdMap<ViewportState>> {
+impl<'a> serde::Serialize for ViewportsSerialize<'a> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut serialize_map = serializer.serialize_map(Some(self.0.len()))?;
-        for (id, state) in self.0 {
-            serialize_map.serialize_entry(id, &SnapshotSerialize(state))?;
+        let mut serialize_map = serializer.serialize_map(Some(self.viewports.len()))?;
+        for (id, state) in self.viewports {
+            let (input, hits) = match self.redaction {
+                Some(redaction) => redaction.redact(*id, &state.input, &state.hits),
+                None => (Cow::Borrowed(&state.input), Cow::Borrowed(&state.hits)),
+            };
+
+            let redacted = matches!(input, Cow::Owned(_));
+            let replay_pass = self
+                .replay_pass
+                .filter(|_| *id == self.last_viewport && !redacted);
+            let input = match replay_pass {
+                Some(pass) if pass == self.pass_count => ViewportInputBorrow::Unchanged,
+                Some(pass) if pass.wrapping_add(1) == self.pass_count => ViewportInputBorrow::Raw {
+                    raw: &state.input.raw,
+                    requested_immediate_repaint_prev_pass: state.repaint.prev_pass_paint_delay
+                        == std::time::Duration::ZERO,
+                    pixels_per_point: state.input.pixels_per_point,
+                    options: self.input_options,
+                },
+                _ => ViewportInputBorrow::Full {
+                    input: &input,
+                    redacted,
+                },
+            };
+
+            let withhold_clipboard = self.owned_layers.is_some()
+                && state.output.commands.iter().any(is_clipboard_command);
+            let withhold_events = self.layers.is_some() && !state.output.events.is_empty();
+            let output = if withhold_clipboard || withhold_events {
+                let mut output = state.output.clone();
+                if withhold_clipboard {
+                    output.commands.retain(|x| !is_clipboard_command(x));
+                }
+                if withhold_events {
+                    output.events.clear();
+                }
+                Cow::Owned(output)
+            } else {
+                Cow::Borrowed(&state.output)
+            };
+
+            serialize_map.serialize_entry(
+                id,
+                &ViewportSerialize {
+                    state,
+                    input,
+                    hits: &hits,
+                    layers: self.layers,
+                    output: &output,
+                },
+            )?;
         }
         serialize_map.end()
     }
 }
 
+/// Serializes a single viewport, using the given input, widget hits and output.
+struct ViewportSerialize<'a> {
+    /// The viewport to serialize.
+    state: &'a ViewportState,
+    /// The input that the recipient may observe.
+    input: ViewportInputBorrow<'a>,
+    /// The widget hits that the recipient may observe.
+    hits: &'a WidgetHits,
+    /// The layers whose shapes, widgets and AccessKit nodes are sent, or `None`
+    /// if those of every layer are sent.
+    layers: Option<&'a std::collections::HashSet<LayerId>>,
+    /// The platform output that the recipient may observe.
+    output: &'a PlatformOutput,
+}
+
+impl<'a> serde::Serialize for ViewportSerialize<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut serialize_tuple = serializer.serialize_tuple(ViewportStateSnapshot::FIELDS)?;
+        serialize_tuple.serialize_element(&self.state.class)?;
+        //serialize_tuple.serialize_element(&self.state.builder)?;
+        serialize_tuple.serialize_element(&self.input)?;
+        let this_pass = visible_pass_state(&self.state.this_pass, self.layers);
+        let prev_pass = visible_pass_state(&self.state.prev_pass, self.layers);
+        serialize_tuple.serialize_element(&*this_pass)?;
+        serialize_tuple.serialize_element(&*prev_pass)?;
+        serialize_tuple.serialize_element(&self.state.used)?;
+        serialize_tuple.serialize_element(self.hits)?;
+        serialize_tuple.serialize_element(&self.state.interact_widgets)?;
+        serialize_tuple.serialize_element(&GraphicsSerialize {
+            graphics: &self.state.graphics,
+            layers: self.layers,
+        })?;
+        serialize_tuple.serialize_element(self.output)?;
+        serialize_tuple.serialize_element(&self.state.commands)?;
+        serialize_tuple.serialize_element(&self.state.num_multipass_in_row)?;
+        serialize_tuple.end()
+    }
+}
+
+/// Gets the pass state that a recipient may observe. If only some layers are visible, the
+/// widget rectangles, widget information and AccessKit nodes of the other layers are withheld.
+fn visible_pass_state<'a>(
+    pass: &'a PassState,
+    layers: Option<&std::collections::HashSet<LayerId>>,
+) -> Cow<'a, PassState> {
+    let Some(layers) = layers else {
+        return Cow::Borrowed(pass);
+    };
+
+    let visible = |id: &Id| {
+        pass.widgets
+            .by_id
+            .get(id)
+            .is_none_or(|(_, x)| layers.contains(&x.layer_id))
+    };
+    let mut result = pass.clone();
+    result.widgets.infos.retain(|id, _| visible(id));
+    result
+        .widgets
+        .by_layer
+        .retain(|layer, _| layers.contains(layer));
+    result
+        .widgets
+        .by_id
+        .retain(|_, (_, x)| layers.contains(&x.layer_id));
+    #[cfg(feature = "accesskit")]
+    if let Some(state) = &mut result.accesskit_state {
+        state.nodes.retain(|id, _| visible(id));
+    }
+    Cow::Owned(result)
+}
+
 impl<'a> serde::Serialize for SnapshotSerialize<'a, GraphicLayers> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         let mut serialize_tuple = serializer.serialize_tuple(self.0.as_inner().len())?;
@@ -409,6 +727,49 @@ impl<'a> serde::Serialize for SnapshotSerialize<'a, GraphicLayers> {
     }
 }
 
+/// Serializes the shapes of a viewport, withholding the shapes of layers which are not visible.
+struct GraphicsSerialize<'a> {
+    /// The shapes to serialize.
+    graphics: &'a GraphicLayers,
+    /// The layers whose shapes are sent, or `None` if the shapes of every layer are sent.
+    layers: Option<&'a std::collections::HashSet<LayerId>>,
+}
+
+impl<'a> serde::Serialize for GraphicsSerialize<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(layers) = self.layers else {
+            return SnapshotSerialize(self.graphics).serialize(serializer);
+        };
+
+        let paint_lists = self.graphics.as_inner();
+        let mut serialize_tuple = serializer.serialize_tuple(paint_lists.len())?;
+        for (paint_lists, order) in paint_lists.iter().zip(crate::ORDERS) {
+            let visible = paint_lists
+                .iter()
+                .filter(|(id, _)| layers.contains(&LayerId::new(order, **id)))
+                .map(|(id, paint_list)| (id, SnapshotSerialize(paint_list)))
+                .collect::<Vec<_>>();
+            serialize_tuple.serialize_element(&SerializeMapEntries(&visible))?;
+        }
+        serialize_tuple.end()
+    }
+}
+
+/// Serializes a list of entries as a map.
+struct SerializeMapEntries<'a, K, V>(&'a [(K, V)]);
+
+impl<'a, K: serde::Serialize, V: serde::Serialize> serde::Serialize
+    for SerializeMapEntries<'a, K, V>
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut serialize_map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in self.0 {
+            serialize_map.serialize_entry(key, value)?;
+        }
+        serialize_map.end()
+    }
+}
+
 impl<'a> serde::Serialize for SnapshotSerialize<'a, IdMap<PaintList>> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         let mut serialize_map = serializer.serialize_map(Some(self.0.len()))?;
@@ -584,8 +945,17 @@ impl<'de> serde::de::Visitor<'de> for SnapshotDeserializeVisitor<ContextSnapshot
             .ok_or_else(|| serde::de::Error::invalid_length(7, &self))?
             .0;
 
+        let accesskit_enabled = seq
+            .next_element()?
+            .ok_or_else(|| serde::de::Error::invalid_length(8, &self))?;
+
+        let drag_payload = seq
+            .next_element()?
+            .ok_or_else(|| serde::de::Error::invalid_length(9, &self))?;
+
         Ok(SnapshotDeserialize(ContextSnapshot {
             deltas,
+            drag_payload,
             font_definitions,
             memory,
             options,
@@ -593,6 +963,7 @@ impl<'de> serde::de::Visitor<'de> for SnapshotDeserializeVisitor<ContextSnapshot
             new_zoom_factor,
             last_viewport,
             viewports,
+            accesskit_enabled,
         }))
     }
 }
@@ -655,7 +1026,7 @@ impl<'de> serde::de::Visitor<'de> for SnapshotDeserializeVisitor<MemorySnapshot>
             areas,
             interactions,
             focus,
-            popups
+            popups,
         }))
     }
 }
@@ -1054,7 +1425,9 @@ impl<'de> serde::de::Visitor<'de> for SnapshotDeserializeVisitor<epaint::Shape>
             7 => seq
                 .next_element::<SnapshotDeserialize<epaint::TextShape>>()?
                 .map(|x| epaint::Shape::Text(x.0)),
-            8 => seq.next_element::<std::sync::Arc<epaint::Mesh>>()?.map(epaint::Shape::Mesh),
+            8 => seq
+                .next_element::<std::sync::Arc<epaint::Mesh>>()?
+                .map(epaint::Shape::Mesh),
             9 => seq
                 .next_element::<epaint::QuadraticBezierShape>()?
                 .map(epaint::Shape::QuadraticBezier),
@@ -1146,4 +1519,33 @@ impl<'de> serde::de::Visitor<'de> for SnapshotDeserializeVisitor<epaint::TextSha
             angle,
         }))
     }
-}
\ No newline at end of file
+}
+
+/// Whether the command places text or an image on the clipboard.
+pub(super) fn is_clipboard_command(command: &egui::OutputCommand) -> bool {
+    matches!(
+        command,
+        egui::OutputCommand::CopyText(_) | egui::OutputCommand::CopyImage(_)
+    )
+}
+
+/// Serializes shapes with snapshot-specific logic, for use with `#[serde(with)]`.
+pub(crate) mod shape {
+    use super::*;
+
+    /// Serializes the given shape.
+    pub fn serialize<S: serde::Serializer>(
+        shape: &epaint::Shape,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&SnapshotSerialize(shape), serializer)
+    }
+
+    /// Deserializes a shape.
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<epaint::Shape, D::Error> {
+        <SnapshotDeserialize<epaint::Shape> as serde::Deserialize>::deserialize(deserializer)
+            .map(|x| x.0)
+    }
+}
@@ -0,0 +1,218 @@
+use crate::*;
+use serde::de::*;
+
+/// Places a widget which is implemented by the host, such as a native file picker or an
+/// asset browser, into a plugin's layout. The plugin reserves space for the widget, and
+/// the host draws the widget into that space after the transaction ends. Changes that the
+/// user makes are delivered to the plugin during its next transaction, so the widget's
+/// value and response lag the host by one frame.
+pub struct HostWidget<'a, T> {
+    /// The size of the space to reserve for the widget.
+    desired_size: Vec2,
+    /// The name under which the host registered the widget.
+    name: &'a str,
+    /// The value that the widget displays and edits.
+    value: &'a mut T,
+}
+
+impl<'a, T: Serialize + DeserializeOwned> HostWidget<'a, T> {
+    /// Creates a widget for the implementation that the host registered under the given
+    /// name. The value is sent to the host, which may change it.
+    pub fn new(name: &'a str, value: &'a mut T) -> Self {
+        Self {
+            desired_size: vec2(200.0, 20.0),
+            name,
+            value,
+        }
+    }
+
+    /// Sets the size of the space to reserve for the widget.
+    pub fn desired_size(mut self, desired_size: Vec2) -> Self {
+        self.desired_size = desired_size;
+        self
+    }
+
+    /// Adds the widget to the UI, returning the response that the host produced for it
+    /// during the previous frame. If the host changed the value, the response is marked
+    /// as changed.
+    pub fn show(self, ui: &mut Ui) -> InnerResponse<HostWidgetResponse> {
+        let (rect, mut response) = ui.allocate_exact_size(self.desired_size, Sense::hover());
+
+        let host_response = ui
+            .ctx()
+            .data_mut(|x| {
+                x.get_temp_mut_or_default::<HostWidgetResponses>(Id::NULL)
+                    .0
+                    .remove(&response.id)
+            })
+            .unwrap_or_default();
+
+        if let Some(value) = host_response.decode() {
+            *self.value = value;
+            response.mark_changed();
+        }
+
+        let placement = HostWidgetPlacement {
+            clip_rect: ui.clip_rect(),
+            enabled: ui.is_enabled(),
+            id: response.id,
+            layer: ui.layer_id(),
+            name: self.name.to_owned(),
+            rect,
+            value: ron::to_string(self.value).expect("Failed to encode widget value."),
+        };
+        ui.ctx().data_mut(|x| {
+            x.get_temp_mut_or_default::<HostWidgetQueue>(Id::NULL)
+                .0
+                .push(placement)
+        });
+
+        InnerResponse::new(host_response, response)
+    }
+}
+
+impl<'a, T: Serialize + DeserializeOwned> Widget for HostWidget<'a, T> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        self.show(ui).response
+    }
+}
+
+/// Stores the host's responses for the current transaction, so that widgets may find them.
+/// Responses are kept until a widget receives them, so that they are not lost if the
+/// plugin performs another transaction before showing the widget again.
+pub(crate) fn add_responses(context: &Context, responses: Vec<(Id, HostWidgetResponse)>) {
+    context.data_mut(|x| {
+        x.get_temp_mut_or_default::<HostWidgetResponses>(Id::NULL)
+            .0
+            .extend(responses)
+    });
+}
+
+/// Removes all host widgets which were placed in the context.
+pub(crate) fn take_placements(context: &Context) -> Vec<HostWidgetPlacement> {
+    context.data_mut(|x| {
+        x.remove_temp::<HostWidgetQueue>(Id::NULL)
+            .unwrap_or_default()
+            .0
+    })
+}
+
+/// The host widgets which will be drawn at the end of the current transaction.
+#[derive(Clone, Default)]
+struct HostWidgetQueue(Vec<HostWidgetPlacement>);
+
+/// The responses that the host produced for widgets which have not yet received them.
+#[derive(Clone, Default)]
+struct HostWidgetResponses(IdMap<HostWidgetResponse>);
+
+/// The response that the host produced for a [`HostWidget`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HostWidgetResponse {
+    /// Whether the user clicked the widget.
+    pub clicked: bool,
+    /// Whether the pointer was over the widget.
+    pub hovered: bool,
+    /// The new value of the widget, encoded as RON, if the user changed it.
+    value: Option<String>,
+}
+
+impl HostWidgetResponse {
+    /// Whether the user changed the widget's value.
+    pub fn changed(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// Decodes the new value of the widget, if the user changed it.
+    fn decode<T: DeserializeOwned>(&self) -> Option<T> {
+        ron::from_str(self.value.as_ref()?).ok()
+    }
+}
+
+/// Describes where a plugin placed a host widget, and the value that it should display.
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostWidgetPlacement {
+    /// The region outside of which the widget should not be drawn.
+    pub clip_rect: Rect,
+    /// Whether the widget accepts input.
+    pub enabled: bool,
+    /// The identifier of the widget.
+    pub id: Id,
+    /// The layer into which the widget is drawn.
+    pub layer: LayerId,
+    /// The name under which the host registered the widget.
+    pub name: String,
+    /// The space that the plugin reserved for the widget.
+    pub rect: Rect,
+    /// The value that the widget should display, encoded as RON.
+    value: String,
+}
+
+impl HostWidgetPlacement {
+    /// Draws the widget into the context with the given implementation, which may change
+    /// the widget's value. Returns the response that will be delivered to the plugin.
+    pub fn show<T: Serialize + DeserializeOwned>(
+        &self,
+        context: &Context,
+        widget: impl FnOnce(&mut Ui, &mut T) -> Response,
+    ) -> HostWidgetResponse {
+        let Ok(mut value) = ron::from_str::<T>(&self.value) else {
+            return HostWidgetResponse::default();
+        };
+
+        let mut builder = UiBuilder::new().layer_id(self.layer).max_rect(self.rect);
+        if !self.enabled {
+            builder = builder.disabled();
+        }
+        let mut ui = Ui::new(context.clone(), self.id, builder);
+        ui.set_clip_rect(self.clip_rect.intersect(self.rect));
+
+        let response = widget(&mut ui, &mut value);
+        HostWidgetResponse {
+            clicked: response.clicked(),
+            hovered: response.hovered(),
+            value: response
+                .changed()
+                .then(|| ron::to_string(&value).ok())
+                .flatten(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shows a checkbox which is implemented by the host.
+    fn show_checkbox(context: &Context, value: &mut bool) -> bool {
+        let mut changed = false;
+        let _ = context.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                changed = HostWidget::new("checkbox", value).ui(ui).changed();
+            });
+        });
+        changed
+    }
+
+    #[test]
+    fn responses_survive_transactions_without_widgets() {
+        let context = Context::default();
+        let mut value = false;
+        show_checkbox(&context, &mut value);
+        let placement = take_placements(&context)
+            .pop()
+            .expect("Failed to place widget.");
+
+        let response = HostWidgetResponse {
+            clicked: true,
+            hovered: true,
+            value: Some(ron::to_string(&true).expect("Failed to encode value.")),
+        };
+        add_responses(&context, vec![(placement.id, response)]);
+        add_responses(&context, Vec::new());
+
+        assert!(show_checkbox(&context, &mut value));
+        assert!(value);
+        assert!(!show_checkbox(&context, &mut value));
+    }
+}